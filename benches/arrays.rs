@@ -0,0 +1,122 @@
+//! Compares the bulk conversion of numeric arrays with encoding them element by element.
+
+use std::io::{self, BufReader, BufWriter, Read, Write};
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rpc_lib::{XdrDeserialize, XdrSerialize};
+
+const LENGTHS: [usize; 3] = [16, 1024, 65536];
+
+/// Number without the bulk conversion of arrays, like every element type before it was added
+#[derive(Clone, Copy)]
+struct Elementwise<T>(T);
+
+impl<T: XdrSerialize> XdrSerialize for Elementwise<T> {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn serialize(&self, writer: impl Write) -> io::Result<()> {
+        self.0.serialize(writer)
+    }
+
+    fn encode_into(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.encode_into(buf)
+    }
+}
+
+impl<T: XdrDeserialize> XdrDeserialize for Elementwise<T> {
+    fn deserialize(reader: impl Read) -> io::Result<Self> {
+        T::deserialize(reader).map(Elementwise)
+    }
+
+    fn decode_from(bytes: &[u8]) -> io::Result<(Self, usize)> {
+        T::decode_from(bytes).map(|(value, read)| (Elementwise(value), read))
+    }
+}
+
+fn bench_type<T>(c: &mut Criterion, name: &str, value: impl Fn(usize) -> T)
+where
+    T: XdrSerialize + XdrDeserialize + Copy,
+{
+    let mut group = c.benchmark_group(name);
+    for len in LENGTHS {
+        let bulk: Vec<T> = (0..len).map(&value).collect();
+        let elementwise: Vec<Elementwise<T>> = bulk.iter().copied().map(Elementwise).collect();
+        let bytes = bulk.serialize_to_vec().unwrap();
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+
+        // Through a buffered writer, like requests of `RpcClient`
+        group.bench_with_input(BenchmarkId::new("serialize/bulk", len), &bulk, |b, bulk| {
+            let mut writer = BufWriter::new(io::sink());
+            b.iter(|| black_box(bulk).serialize(&mut writer).unwrap());
+        });
+        group.bench_with_input(
+            BenchmarkId::new("serialize/elementwise", len),
+            &elementwise,
+            |b, elementwise| {
+                let mut writer = BufWriter::new(io::sink());
+                b.iter(|| black_box(elementwise).serialize(&mut writer).unwrap());
+            },
+        );
+
+        let mut buf = vec![0; bytes.len()];
+        group.bench_with_input(
+            BenchmarkId::new("encode_into/bulk", len),
+            &bulk,
+            |b, bulk| {
+                b.iter(|| black_box(bulk).encode_into(&mut buf).unwrap());
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("encode_into/elementwise", len),
+            &elementwise,
+            |b, elementwise| {
+                b.iter(|| black_box(elementwise).encode_into(&mut buf).unwrap());
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("deserialize/bulk", len),
+            &bytes,
+            |b, bytes| {
+                b.iter(|| Vec::<T>::deserialize(BufReader::new(black_box(&bytes[..]))).unwrap());
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("deserialize/elementwise", len),
+            &bytes,
+            |b, bytes| {
+                b.iter(|| {
+                    Vec::<Elementwise<T>>::deserialize(BufReader::new(black_box(&bytes[..])))
+                        .unwrap()
+                });
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("decode_from/bulk", len),
+            &bytes,
+            |b, bytes| {
+                b.iter(|| Vec::<T>::decode_from(black_box(bytes)).unwrap());
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("decode_from/elementwise", len),
+            &bytes,
+            |b, bytes| {
+                b.iter(|| Vec::<Elementwise<T>>::decode_from(black_box(bytes)).unwrap());
+            },
+        );
+    }
+    group.finish();
+}
+
+fn arrays(c: &mut Criterion) {
+    bench_type(c, "u32", |i| i as u32);
+    bench_type(c, "f32", |i| i as f32 * 0.5);
+    bench_type(c, "f64", |i| i as f64 * 0.25);
+}
+
+criterion_group!(benches, arrays);
+criterion_main!(benches);
@@ -0,0 +1,449 @@
+// Copyright 2022 Philipp Fensch
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! # Rpc-lib-build
+//!
+//! Rpc-lib-build compiles an RPC-Definition in the ONC-RPC format into Rust-code from a build
+//! script. It generates the same code as `rpc_lib::include_rpcl`, but writes it into a file, so
+//! that it can be inspected, diffed and documented.
+//!
+//! # Example
+//!
+//! Generates `$OUT_DIR/math.rs` from `math.x`.
+//! ```rust,no_run
+//! // build.rs
+//! rpc_lib_build::Config::new()
+//!     .file("math.x")
+//!     .generate()
+//!     .expect("Failed to generate code for math.x");
+//! ```
+//!
+//! The generated file contains a client struct named after the program (`MathClient` for
+//! `program MATH`) and all datatypes of the RPC-Definition. A server trait (`MathServer`) can be
+//! generated with [`Config::server`].
+//! ```rust,ignore
+//! // src/main.rs
+//! #[allow(non_snake_case, non_camel_case_types)]
+//! mod math {
+//!     include!(concat!(env!("OUT_DIR"), "/math.rs"));
+//! }
+//!
+//! fn main() {
+//!     let mut rpc = math::MathClient::new("127.0.0.1").expect("Server not available");
+//!     let result = rpc.ADD(&1, &2).expect("Rpc call failed");
+//!     println!("ADD returned: {}", result);
+//! }
+//! ```
+#![forbid(unsafe_code)]
+#![warn(rust_2018_idioms)]
+
+mod parser;
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+
+pub use parser::Error;
+
+/// Configuration of the code generator, used from build scripts and `rpcgen-rs`.
+///
+/// By default the datatypes and the client are generated.
+#[derive(Debug, Clone)]
+pub struct Config {
+    files: Vec<PathBuf>,
+    out_dir: Option<PathBuf>,
+    client_name: Option<String>,
+    server_name: Option<String>,
+    types: bool,
+    client: bool,
+    server: bool,
+    stubs: bool,
+    sample_main: bool,
+    lists_as_vec: bool,
+    strings_as_bytes: bool,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            files: Vec::new(),
+            out_dir: None,
+            client_name: None,
+            server_name: None,
+            types: true,
+            client: true,
+            server: false,
+            stubs: false,
+            sample_main: false,
+            lists_as_vec: false,
+            strings_as_bytes: false,
+        }
+    }
+}
+
+impl Config {
+    pub fn new() -> Config {
+        Config::default()
+    }
+
+    /// Adds a .x-File to generate code for. The code is written to `<out_dir>/<file stem>.rs`.
+    pub fn file(&mut self, path: impl AsRef<Path>) -> &mut Config {
+        self.files.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Sets the directory the generated files are written to. Defaults to `$OUT_DIR`.
+    pub fn out_dir(&mut self, path: impl AsRef<Path>) -> &mut Config {
+        self.out_dir = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Sets the name of the generated client struct. Defaults to the program name in UpperCamelCase
+    /// followed by `Client`.
+    pub fn client_name(&mut self, name: impl Into<String>) -> &mut Config {
+        self.client_name = Some(name.into());
+        self
+    }
+
+    /// Sets the name of the generated server trait. Defaults to the program name in UpperCamelCase
+    /// followed by `Server`. The stubs implement it for `<server name>Impl`.
+    pub fn server_name(&mut self, name: impl Into<String>) -> &mut Config {
+        self.server_name = Some(name.into());
+        self
+    }
+
+    /// Generates the datatypes and their XDR-implementations (`rpcgen -h`/`-c`).
+    pub fn types(&mut self, enable: bool) -> &mut Config {
+        self.types = enable;
+        self
+    }
+
+    /// Generates the client struct with a function for every procedure (`rpcgen -l`).
+    pub fn client(&mut self, enable: bool) -> &mut Config {
+        self.client = enable;
+        self
+    }
+
+    /// Generates the server trait with a function for every procedure and a `dispatch` function
+    /// decoding calls (`rpcgen -m`).
+    pub fn server(&mut self, enable: bool) -> &mut Config {
+        self.server = enable;
+        self
+    }
+
+    /// Generates an implementation of the server trait to be filled in (`rpcgen -Ss`).
+    pub fn stubs(&mut self, enable: bool) -> &mut Config {
+        self.stubs = enable;
+        self
+    }
+
+    /// Generates a `main` function calling the procedures with the client (`rpcgen -Sc`). The
+    /// datatypes and the client are expected in a module named after the .x-File.
+    pub fn sample_main(&mut self, enable: bool) -> &mut Config {
+        self.sample_main = enable;
+        self
+    }
+
+    /// Maps linked lists (`struct entry { ...; entry *next; };`) to `rpc_lib::XdrList<entry>`, a
+    /// `Vec` of the elements without their `next` field. The encoding doesn't change.
+    ///
+    /// Otherwise linked lists are generated as `Option<Box<entry>>` chains, which are serialized,
+    /// deserialized and dropped iteratively. As the nodes implement `Drop`, their fields can't be
+    /// moved out, only taken with `std::mem::take` or cloned.
+    pub fn linked_lists_as_vec(&mut self, enable: bool) -> &mut Config {
+        self.lists_as_vec = enable;
+        self
+    }
+
+    /// Maps `string` to `rpc_lib::XdrString`, which holds arbitrary bytes. The encoding doesn't
+    /// change.
+    ///
+    /// Otherwise strings are generated as `String`, which fails to decode strings that aren't
+    /// valid UTF-8.
+    pub fn strings_as_bytes(&mut self, enable: bool) -> &mut Config {
+        self.strings_as_bytes = enable;
+        self
+    }
+
+    /// Generates and formats the code for all files.
+    pub fn generate(&self) -> io::Result<()> {
+        let out_dir = match &self.out_dir {
+            Some(out_dir) => out_dir.clone(),
+            None => env::var_os("OUT_DIR")
+                .map(PathBuf::from)
+                .ok_or_else(|| io::Error::other("OUT_DIR is not set, use Config::out_dir"))?,
+        };
+        for path in &self.files {
+            println!("cargo:rerun-if-changed={}", path.display());
+            let source = fs::read_to_string(path)?;
+            let code = self.generate_string(&source, &path.display().to_string())?;
+            let file_name = match path.file_stem() {
+                Some(stem) => Path::new(stem).with_extension("rs"),
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("{} is not a file", path.display()),
+                    ))
+                }
+            };
+            fs::write(out_dir.join(file_name), code)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the formatted code for the contents of a .x-File. `path` is used in error messages
+    /// and as the module name of the sample main.
+    pub fn generate_string(&self, source: &str, path: &str) -> io::Result<String> {
+        let options = parser::Options {
+            lists_as_vec: self.lists_as_vec,
+            strings_as_bytes: self.strings_as_bytes,
+        };
+        let (spec, program) = parser::parse(source, options).map_err(|errors| {
+            let messages: Vec<String> =
+                errors.iter().map(|err| err.display(path, source)).collect();
+            io::Error::new(io::ErrorKind::InvalidData, messages.join("\n"))
+        })?;
+        let prefix = upper_camel_case(&program.name);
+        let client_name = match &self.client_name {
+            Some(name) => name.clone(),
+            None => format!("{}Client", prefix),
+        };
+        let server_name = match &self.server_name {
+            Some(name) => name.clone(),
+            None => format!("{}Server", prefix),
+        };
+
+        let mut code = TokenStream::new();
+        if self.types {
+            code.extend(parser::generate_types(&spec));
+        }
+        if self.client {
+            let name = format_ident!("{}", client_name);
+            code.extend(client_code(&name, path, &program, quote!(pub)));
+            code.extend(parser::generate_client(
+                &spec,
+                program.clone(),
+                &client_name,
+            ));
+        }
+        if self.server {
+            code.extend(parser::generate_server(&program, &server_name));
+        }
+        if self.stubs {
+            let impl_name = format!("{}Impl", server_name);
+            code.extend(parser::generate_stubs(&program, &server_name, &impl_name));
+        }
+        if self.sample_main {
+            let module = module_name(path);
+            code.extend(parser::generate_sample_main(
+                &program,
+                &module,
+                &client_name,
+            ));
+        }
+
+        let mut file: syn::File =
+            syn::parse2(code).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        make_public(&mut file);
+        Ok(prettyplease::unparse(&file))
+    }
+}
+
+/// Parses a .x-File and generates its code. The procedures become associated functions of a
+/// struct named `struct_name`, which is generated as well. `path` is used in documentation and
+/// error messages.
+pub fn generate_tokens(
+    x_file: &str,
+    path: &str,
+    struct_name: &str,
+) -> Result<TokenStream, Vec<Error>> {
+    let (spec, program) = parser::parse(x_file, parser::Options::default())?;
+    let name = format_ident!("{}", struct_name);
+    let client = client_code(&name, path, &program, quote!());
+    let code = parser::generate(&spec, program, struct_name);
+    Ok(quote! {
+        #client
+        #code
+    })
+}
+
+fn client_code(
+    name: &syn::Ident,
+    path: &str,
+    program: &parser::Program,
+    vis: TokenStream,
+) -> TokenStream {
+    let prog_num = program.program_number;
+    let ver_num = program.versions[0].version_number;
+    let doc = format!(
+        " Contains connection to Rpc-Service and associated functions as defined in `{}`.",
+        path
+    );
+    let doc_new = format!(
+        "     let mut rpc = {}::new(\"127.0.0.1\").expect(\"Server not available\");",
+        name
+    );
+    quote! {
+        #[doc = #doc]
+        ///
+        /// # Examples
+        ///
+        /// Creates a connection to 127.0.0.1, makes an Rpc-Call and prints the result.
+        /// ```ignore
+        /// fn main() {
+        #[doc = #doc_new]
+        ///     let result = rpc.MY_RPC_PROCEDURE(&1, &2).expect("Rpc call failed");
+        ///     println!("MY_RPC_PROCEDURE returned: {}", result);
+        /// }
+        /// ```
+        #vis struct #name {
+            client: rpc_lib::RpcClient
+        }
+
+        impl #name {
+            /// Creates Connection to requested Rpc-Service.
+            ///
+            /// Connects to Portmapper-Service, gets Port-Number of requested Rpc-Service and
+            /// connects to it.
+            #vis fn new(address: &str) -> std::io::Result<#name> {
+                Ok(#name {
+                    client: rpc_lib::clnt_create(address.parse().unwrap(), #prog_num, #ver_num)?
+                })
+            }
+        }
+    }
+}
+
+/// Makes generated types, their fields and the procedures public, so that they can be used from
+/// outside of the module the generated file is included in.
+fn make_public(file: &mut syn::File) {
+    let public: syn::Visibility = syn::parse_quote!(pub);
+    for item in &mut file.items {
+        match item {
+            syn::Item::Const(item) => item.vis = public.clone(),
+            syn::Item::Type(item) => item.vis = public.clone(),
+            syn::Item::Enum(item) => item.vis = public.clone(),
+            syn::Item::Trait(item) => item.vis = public.clone(),
+            syn::Item::Struct(item) => {
+                if let syn::Visibility::Inherited = item.vis {
+                    item.vis = public.clone();
+                    item.fields
+                        .iter_mut()
+                        .for_each(|field| field.vis = public.clone());
+                }
+            }
+            syn::Item::Impl(item) if item.trait_.is_none() => {
+                for impl_item in &mut item.items {
+                    if let syn::ImplItem::Fn(func) = impl_item {
+                        func.vis = public.clone();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Returns the file stem of `path` as an identifier.
+fn module_name(path: &str) -> String {
+    let stem = Path::new(path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+    stem.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+fn upper_camel_case(name: &str) -> String {
+    name.split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            let first = chars.next().unwrap().to_ascii_uppercase();
+            let rest: String = chars.map(|c| c.to_ascii_lowercase()).collect();
+            format!("{}{}", first, rest)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MATH_X: &str = "struct Pair { int a; int b; };
+        program MATH_PROG {
+            version VERS {
+                int ADD(Pair) = 1;
+            } = 1;
+        } = 67908;";
+
+    #[test]
+    fn test_upper_camel_case() {
+        assert_eq!(upper_camel_case("MATH"), "Math");
+        assert_eq!(upper_camel_case("MATH_PROG"), "MathProg");
+        assert_eq!(upper_camel_case("nfs_program_3"), "NfsProgram3");
+    }
+
+    #[test]
+    fn test_generate_source() {
+        let code = Config::new().generate_string(MATH_X, "math.x").unwrap();
+        assert!(code.contains("pub struct MathProgClient {\n    client: rpc_lib::RpcClient,\n}"));
+        assert!(code.contains("pub struct Pair {\n    pub a: i32,\n    pub b: i32,\n}"));
+        assert!(code.contains("pub fn ADD(&mut self, x0: &Pair) -> std::io::Result<i32> {"));
+    }
+
+    #[test]
+    fn test_strings_as_bytes() {
+        let x_file = "typedef string Name<8>;
+            struct File { string path<>; Name name; };
+            program FS {
+                version VERS {
+                    string LOOKUP(string) = 1;
+                } = 1;
+            } = 1;";
+        let code = Config::new()
+            .strings_as_bytes(true)
+            .generate_string(x_file, "fs.x")
+            .unwrap();
+        assert!(code.contains("pub type Name = rpc_lib::XdrString;"));
+        assert!(code.contains("pub path: rpc_lib::XdrString,"));
+        assert!(code.contains("x0: &rpc_lib::XdrString,"));
+        assert!(code.contains("std::io::Result<rpc_lib::XdrString>"));
+        let code = Config::new().generate_string(x_file, "fs.x").unwrap();
+        assert!(code.contains("pub path: String,"));
+    }
+
+    #[test]
+    fn test_generate_source_error() {
+        let x_file = "program MATH { version VERS { Foo ADD(int) = 1; } = 1; } = 1;";
+        let err = Config::new().generate_string(x_file, "math.x").unwrap_err();
+        assert_eq!(err.to_string(), "math.x:1:31: unknown type `Foo`");
+    }
+
+    #[test]
+    fn test_generate() {
+        // `CARGO_TARGET_TMPDIR` is only set for integration tests
+        let dir = env::temp_dir().join(format!("rpc-lib-build-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("math.x"), MATH_X).unwrap();
+        Config::new()
+            .file(dir.join("math.x"))
+            .out_dir(&dir)
+            .client_name("Math")
+            .generate()
+            .unwrap();
+        let code = fs::read_to_string(dir.join("math.rs")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(code.contains("pub struct Math {"));
+    }
+}
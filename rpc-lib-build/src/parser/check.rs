@@ -0,0 +1,765 @@
+// Copyright 2022 Philipp Fensch
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::{HashMap, HashSet};
+
+use super::constant::Value;
+use super::datatype::DataType;
+use super::declaration::{Declaration, DeclarationType};
+use super::error::{Error, Span};
+use super::program::Program;
+use super::uniondef::{DiscriminantType, Uniondef};
+use super::xdr_spec::Specification;
+
+/// Checks a parsed .x-File for errors, that would otherwise generate invalid Rust-code or make the
+/// code generation panic.
+pub fn check(spec: &Specification, program: &Program) -> Vec<Error> {
+    let mut checker = Checker {
+        spec,
+        errors: Vec::new(),
+    };
+    checker.check_names();
+    checker.check_specification();
+    checker.check_recursion();
+    checker.check_program(program);
+    checker.errors
+}
+
+struct Checker<'a> {
+    spec: &'a Specification,
+    errors: Vec<Error>,
+}
+
+impl Checker<'_> {
+    /// Types and constants share one namespace each. Enum-cases are constants as well.
+    fn check_names(&mut self) {
+        let mut types = HashSet::new();
+        let type_names = self
+            .spec
+            .typedefs
+            .iter()
+            .map(|td| (&td.name, td.span))
+            .chain(self.spec.enums.iter().map(|e| (&e.name, e.span)))
+            .chain(self.spec.structs.iter().map(|s| (&s.name, s.span)))
+            .chain(self.spec.unions.iter().map(|u| (&u.name, u.span)));
+        // Report the definition that comes last in the file
+        let mut type_names: Vec<_> = type_names.collect();
+        type_names.sort_by_key(|(_, span)| span.start);
+        for (name, span) in type_names {
+            if !types.insert(name) {
+                self.errors.push(
+                    Error::new(span, format!("type `{}` is defined multiple times", name))
+                        .at_token(name),
+                );
+            }
+        }
+
+        let mut constants = HashSet::new();
+        let constant_names = self.spec.constants.iter().map(|c| (&c.name, c.span)).chain(
+            self.spec.enums.iter().flat_map(|e| {
+                e.enum_body
+                    .cases
+                    .iter()
+                    .map(move |(name, _)| (name, e.span))
+            }),
+        );
+        let mut constant_names: Vec<_> = constant_names.collect();
+        constant_names.sort_by_key(|(_, span)| span.start);
+        for (name, span) in constant_names {
+            if !constants.insert(name) {
+                self.errors.push(
+                    Error::new(
+                        span,
+                        format!("constant `{}` is defined multiple times", name),
+                    )
+                    .at_token(name),
+                );
+            }
+        }
+    }
+
+    fn check_specification(&mut self) {
+        for typedef in &self.spec.typedefs {
+            self.check_data_type(&typedef.orig_type, typedef.span);
+            self.check_decl_type(&typedef.decl_type, typedef.span);
+            if let Some(max_len) = &typedef.max_len {
+                self.check_max_len(max_len, typedef.span);
+            }
+        }
+        for enumdef in &self.spec.enums {
+            let mut values: HashMap<i64, &str> = HashMap::new();
+            let mut cases: HashMap<&str, i64> = HashMap::new();
+            for (name, value) in &enumdef.enum_body.cases {
+                // Cases can refer to earlier cases of the same enum
+                let earlier = match value {
+                    Value::Named { name } => cases.get(name.as_str()).copied(),
+                    Value::Numeric { .. } => None,
+                };
+                let Some(val) = earlier.or_else(|| self.resolve_value(value, enumdef.span)) else {
+                    continue;
+                };
+                cases.insert(name, val);
+                if i32::try_from(val).is_err() {
+                    self.errors.push(
+                        Error::new(
+                            enumdef.span,
+                            format!("value {} of `{}` doesn't fit into an int", val, name),
+                        )
+                        .at_token(name),
+                    );
+                }
+                if let Some(other) = values.insert(val, name) {
+                    self.errors.push(
+                        Error::new(
+                            enumdef.span,
+                            format!(
+                                "value {} of `{}` collides with `{}` in enum `{}`",
+                                val, name, other, enumdef.name
+                            ),
+                        )
+                        .at_token(name),
+                    );
+                }
+            }
+        }
+        for structdef in &self.spec.structs {
+            for field in &structdef.struct_body.fields {
+                self.check_declaration(field, structdef.span);
+            }
+        }
+        for uniondef in &self.spec.unions {
+            self.check_union(uniondef);
+        }
+    }
+
+    fn check_union(&mut self, uniondef: &Uniondef) {
+        let span = uniondef.span;
+        let union_body = &uniondef.union_body;
+        let enumdef = match &union_body.discriminant {
+            DiscriminantType::Enum { name } => {
+                let enumdef = self.spec.enums.iter().find(|e| &e.name == name);
+                if enumdef.is_none() {
+                    let message = if self.is_defined_type(name) {
+                        format!("`{}` can't be a union discriminant, it isn't an enum", name)
+                    } else {
+                        format!("unknown type `{}`", name)
+                    };
+                    self.errors.push(Error::new(span, message).at_token(name));
+                }
+                enumdef
+            }
+            DiscriminantType::Unsupported { decl } => {
+                let token = decl.split(' ').next().unwrap_or_default();
+                self.errors.push(
+                    Error::new(
+                        span,
+                        format!(
+                            "`{}` can't be a union discriminant, it must be an int, unsigned int, bool or enum",
+                            decl
+                        ),
+                    )
+                    .at_token(token),
+                );
+                None
+            }
+            _ => None,
+        };
+        let mut values = HashSet::new();
+        let mut label_spans = union_body.label_spans.iter().copied();
+        for (case_vals, decl) in &union_body.cases {
+            for value in case_vals {
+                let label_span = label_spans.next().unwrap_or(span);
+                let val = match (&union_body.discriminant, value) {
+                    (DiscriminantType::Enum { name: enum_name }, _) => {
+                        let Some(enumdef) = enumdef else {
+                            continue;
+                        };
+                        let case = match value {
+                            Value::Named { name } => enumdef
+                                .enum_body
+                                .cases
+                                .iter()
+                                .find(|(case, _)| case == name),
+                            Value::Numeric { val: _ } => None,
+                        };
+                        match case {
+                            Some((_, val)) => self.resolve_value(val, span),
+                            None => {
+                                let label = match value {
+                                    Value::Named { name } => name.clone(),
+                                    Value::Numeric { val } => val.to_string(),
+                                };
+                                self.errors.push(
+                                    Error::new(
+                                        span,
+                                        format!(
+                                            "case `{}` of union `{}` isn't a case of enum `{}`",
+                                            label, uniondef.name, enum_name
+                                        ),
+                                    )
+                                    .at_token(label),
+                                );
+                                None
+                            }
+                        }
+                    }
+                    (DiscriminantType::Boolean, Value::Named { name }) if name == "TRUE" => Some(1),
+                    (DiscriminantType::Boolean, Value::Named { name }) if name == "FALSE" => {
+                        Some(0)
+                    }
+                    (_, Value::Named { name })
+                        if self.spec.constants.iter().all(|c| &c.name != name)
+                            && self.spec.enums.iter().any(|e| {
+                                e.enum_body.cases.iter().any(|(case, _)| case == name)
+                            }) =>
+                    {
+                        self.errors.push(
+                            Error::new(
+                                span,
+                                format!(
+                                    "case `{}` of union `{}` is an enum case, but the discriminant isn't an enum",
+                                    name, uniondef.name
+                                ),
+                            )
+                            .at_token(name),
+                        );
+                        None
+                    }
+                    _ => self.resolve_value(value, span),
+                };
+                if let Some(val) = val {
+                    let fits = match &union_body.discriminant {
+                        DiscriminantType::Int | DiscriminantType::Enum { name: _ } => {
+                            i32::try_from(val).is_ok()
+                        }
+                        DiscriminantType::UnsignedInt => u32::try_from(val).is_ok(),
+                        DiscriminantType::Boolean => val == 0 || val == 1,
+                        DiscriminantType::Unsupported { .. } => true,
+                    };
+                    if !fits {
+                        self.errors.push(Error::new(
+                            label_span,
+                            format!(
+                                "case {} of union `{}` doesn't fit into the discriminant",
+                                val, uniondef.name
+                            ),
+                        ));
+                    }
+                    if !values.insert(val) {
+                        self.errors.push(Error::new(
+                            label_span,
+                            format!(
+                                "case {} of union `{}` is used multiple times",
+                                val, uniondef.name
+                            ),
+                        ));
+                    }
+                }
+            }
+            self.check_declaration(decl, span);
+        }
+        if let Some(default) = &union_body.default {
+            self.check_declaration(default, span);
+        }
+    }
+
+    /// Types can only contain themselves through optional data or variable-length arrays.
+    /// Otherwise they would be infinitely large.
+    fn check_recursion(&mut self) {
+        let mut contained: HashMap<&str, (Vec<&str>, Span)> = HashMap::new();
+        for typedef in &self.spec.typedefs {
+            let mut types = Vec::new();
+            if let DataType::TypeDef { name } = &typedef.orig_type {
+                if is_direct(&typedef.decl_type) {
+                    types.push(name.as_str());
+                }
+            }
+            contained.insert(&typedef.name, (types, typedef.span));
+        }
+        for structdef in &self.spec.structs {
+            let types = directly_contained(structdef.struct_body.fields.iter());
+            contained.insert(&structdef.name, (types, structdef.span));
+        }
+        for uniondef in &self.spec.unions {
+            let union_body = &uniondef.union_body;
+            let decls = union_body
+                .cases
+                .iter()
+                .map(|(_, decl)| decl)
+                .chain(union_body.default.as_deref());
+            contained.insert(&uniondef.name, (directly_contained(decls), uniondef.span));
+        }
+
+        let mut recursive: Vec<(&str, Span)> = contained
+            .iter()
+            .filter(|(name, _)| contains_itself(&contained, name))
+            .map(|(name, (_, span))| (*name, *span))
+            .collect();
+        recursive.sort_by_key(|(_, span)| span.start);
+        for (name, span) in recursive {
+            self.errors.push(
+                Error::new(
+                    span,
+                    format!(
+                        "type `{}` contains itself without an optional-data (`*`) indirection",
+                        name
+                    ),
+                )
+                .at_token(name),
+            );
+        }
+    }
+
+    fn check_program(&mut self, program: &Program) {
+        for version in program.versions.iter().skip(1) {
+            self.errors.push(Error::new(
+                version.span,
+                "multiple versions in one program are not supported",
+            ));
+        }
+        let mut version_numbers = HashSet::new();
+        for version in &program.versions {
+            if !version_numbers.insert(version.version_number) {
+                self.errors.push(Error::new(
+                    version.span,
+                    format!(
+                        "version number {} is used multiple times",
+                        version.version_number
+                    ),
+                ));
+            }
+
+            let mut names = HashSet::new();
+            let mut numbers: HashMap<i64, &str> = HashMap::new();
+            for procedure in &version.procedures {
+                for arg in &procedure.args {
+                    self.check_data_type(arg, procedure.span);
+                }
+                self.check_data_type(&procedure.return_type, procedure.span);
+
+                if !names.insert(&procedure.name) {
+                    self.errors.push(
+                        Error::new(
+                            procedure.span,
+                            format!("procedure `{}` is defined multiple times", procedure.name),
+                        )
+                        .at_token(&procedure.name),
+                    );
+                }
+
+                let Some(val) = self.resolve_value(&procedure.num, procedure.span) else {
+                    continue;
+                };
+                if u32::try_from(val).is_err() {
+                    self.errors.push(
+                        Error::new(
+                            procedure.span,
+                            format!(
+                                "procedure number {} of `{}` doesn't fit into an unsigned int",
+                                val, procedure.name
+                            ),
+                        )
+                        .at_token(&procedure.name),
+                    );
+                }
+                if let Some(other) = numbers.insert(val, &procedure.name) {
+                    self.errors.push(
+                        Error::new(
+                            procedure.span,
+                            format!(
+                                "procedure number {} of `{}` is already used by `{}`",
+                                val, procedure.name, other
+                            ),
+                        )
+                        .at_token(&procedure.name),
+                    );
+                }
+            }
+        }
+    }
+
+    fn check_declaration(&mut self, decl: &Declaration, span: Span) {
+        self.check_data_type(&decl.data_type, span);
+        self.check_decl_type(&decl.decl_type, span);
+        if let Some(max_len) = &decl.max_len {
+            self.check_max_len(max_len, span);
+        }
+    }
+
+    fn check_max_len(&mut self, max_len: &Value, span: Span) {
+        if let Some(len) = self.resolve_value(max_len, span) {
+            if u32::try_from(len).is_err() {
+                self.errors
+                    .push(Error::new(span, format!("invalid maximum length {}", len)));
+            }
+        }
+    }
+
+    fn check_decl_type(&mut self, decl_type: &DeclarationType, span: Span) {
+        if let DeclarationType::FixedlenArray { length } = decl_type {
+            if let Some(len) = self.resolve_value(length, span) {
+                if usize::try_from(len).is_err() {
+                    self.errors
+                        .push(Error::new(span, format!("invalid array length {}", len)));
+                }
+            }
+        }
+    }
+
+    fn check_data_type(&mut self, data_type: &DataType, span: Span) {
+        match data_type {
+            DataType::TypeDef { name } if !self.is_defined_type(name) => {
+                self.errors
+                    .push(Error::new(span, format!("unknown type `{}`", name)).at_token(name));
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns the numeric value of `value`. Reports an error if it names an unknown constant.
+    fn resolve_value(&mut self, value: &Value, span: Span) -> Option<i64> {
+        match value {
+            Value::Numeric { val } => Some(*val),
+            Value::Named { name } => {
+                let constant = self.spec.constants.iter().find(|c| &c.name == name);
+                match constant.map(|c| &c.value) {
+                    Some(Value::Numeric { val }) => Some(*val),
+                    _ => {
+                        self.errors.push(
+                            Error::new(span, format!("unknown constant `{}`", name)).at_token(name),
+                        );
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    fn is_defined_type(&self, name: &str) -> bool {
+        name == "opaque"
+            || self.spec.typedefs.iter().any(|td| td.name == name)
+            || self.spec.get_type_specification(name).is_some()
+    }
+}
+
+/// Returns whether a declaration stores its data inline.
+fn is_direct(decl_type: &DeclarationType) -> bool {
+    matches!(
+        decl_type,
+        DeclarationType::TypeNameDecl | DeclarationType::FixedlenArray { .. }
+    )
+}
+
+fn directly_contained<'a>(decls: impl Iterator<Item = &'a Declaration>) -> Vec<&'a str> {
+    decls
+        .filter(|decl| is_direct(&decl.decl_type))
+        .filter_map(|decl| match &decl.data_type {
+            DataType::TypeDef { name } => Some(name.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn contains_itself(contained: &HashMap<&str, (Vec<&str>, Span)>, name: &str) -> bool {
+    let mut visited = HashSet::new();
+    let mut stack = vec![name];
+    while let Some(current) = stack.pop() {
+        let Some((types, _)) = contained.get(current) else {
+            continue;
+        };
+        for ty in types {
+            if *ty == name {
+                return true;
+            }
+            if visited.insert(*ty) {
+                stack.push(ty);
+            }
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_file, RPCLParser, Rule};
+    use pest::Parser;
+
+    fn check_str(s: &str) -> Vec<String> {
+        let (spec, program) = parse_file(s).unwrap();
+        check(&spec, &program)
+            .iter()
+            .map(|err| err.display("test.x", s))
+            .collect()
+    }
+
+    #[test]
+    fn check_valid() {
+        let s = "struct X { int x; opaque y<>; };
+        typedef X Y;
+        program PROG {
+            version VERS {
+                Y FUNC(X) = 1;
+                void FUNC2(void) = 2;
+            } = 1;
+        } = 10;";
+        assert_eq!(check_str(s), Vec::<String>::new());
+    }
+
+    #[test]
+    fn check_unknown_type() {
+        let s = "struct X {
+            int x;
+            Foo y;
+        };
+        program PROG {
+            version VERS {
+                Bar FUNC(X) = 1;
+            } = 1;
+        } = 10;";
+        assert_eq!(
+            check_str(s),
+            vec![
+                "test.x:3:13: unknown type `Foo`",
+                "test.x:7:17: unknown type `Bar`",
+            ]
+        );
+    }
+
+    #[test]
+    fn check_duplicate_procedure_number() {
+        let s = "program PROG {
+            version VERS {
+                void FUNC(void) = 1;
+                void FUNC2(void) = 1;
+            } = 1;
+        } = 10;";
+        assert_eq!(
+            check_str(s),
+            vec!["test.x:4:22: procedure number 1 of `FUNC2` is already used by `FUNC`"]
+        );
+    }
+
+    #[test]
+    fn check_union_case() {
+        let s = "enum Color { RED = 0 };
+        union U switch (int x) {
+            case 1: int a;
+            case FOO: int b;
+            case RED: int c;
+            default: void;
+        };
+        program PROG {
+            version VERS {
+                U FUNC(void) = 1;
+            } = 1;
+        } = 10;";
+        assert_eq!(
+            check_str(s),
+            vec![
+                "test.x:4:18: unknown constant `FOO`",
+                "test.x:5:18: case `RED` of union `U` is an enum case, but the discriminant isn't an enum",
+            ]
+        );
+    }
+
+    #[test]
+    fn check_union_discriminant() {
+        let s = "enum Color { RED = 0, GREEN = 1 };
+        const ONE = 1;
+        union A switch (Color c) {
+            case RED: int a;
+            case BLUE: int b;
+            case 1: int c;
+        };
+        union B switch (bool b) {
+            case TRUE: int a;
+            case 2: int b;
+        };
+        union C switch (unsigned int x) {
+            case ONE: int a;
+            case -1: int b;
+        };
+        union D switch (C c) {
+            case 1: int a;
+        };
+        program PROG {
+            version VERS {
+                void FUNC(void) = 1;
+            } = 1;
+        } = 10;";
+        assert_eq!(
+            check_str(s),
+            vec![
+                "test.x:5:18: case `BLUE` of union `A` isn't a case of enum `Color`",
+                "test.x:6:18: case `1` of union `A` isn't a case of enum `Color`",
+                "test.x:10:18: case 2 of union `B` doesn't fit into the discriminant",
+                "test.x:14:18: case -1 of union `C` doesn't fit into the discriminant",
+                "test.x:16:25: `C` can't be a union discriminant, it isn't an enum",
+            ]
+        );
+    }
+
+    #[test]
+    fn check_union_unsupported_discriminant() {
+        let s = "union U switch (float x) {
+            case 1: int a;
+        };
+        union V switch (string s<>) {
+            case 1: int a;
+        };
+        union W switch (hyper x) {
+            case 1: int a;
+        };
+        union X switch (unsigned short x) {
+            case 1: int a;
+        };
+        union Y switch (int x[2]) {
+            case 1: int a;
+        };
+        program PROG {
+            version VERS {
+                void FUNC(void) = 1;
+            } = 1;
+        } = 10;";
+        assert_eq!(
+            check_str(s),
+            vec![
+                "test.x:1:17: `float x` can't be a union discriminant, it must be an int, unsigned int, bool or enum",
+                "test.x:4:25: `string s<>` can't be a union discriminant, it must be an int, unsigned int, bool or enum",
+                "test.x:7:25: `hyper x` can't be a union discriminant, it must be an int, unsigned int, bool or enum",
+                "test.x:10:25: `unsigned short x` can't be a union discriminant, it must be an int, unsigned int, bool or enum",
+                "test.x:13:25: `int x[2]` can't be a union discriminant, it must be an int, unsigned int, bool or enum",
+            ]
+        );
+    }
+
+    #[test]
+    fn check_parse_error() {
+        let s = "program PROG {
+            version VERS {
+                void FUNC(void) = ;
+            } = 1;
+        } = 10;";
+        let err = RPCLParser::parse(Rule::file, s).unwrap_err();
+        assert_eq!(
+            Error::from(err).display("test.x", s),
+            "test.x:3:35: expected value, found `;`"
+        );
+    }
+
+    #[test]
+    fn check_duplicate_names() {
+        let s = "const A = 1;
+        struct X { int x; };
+        enum X { A = 2, B = 3 };
+        program PROG {
+            version VERS {
+                void FUNC(void) = 1;
+                void FUNC(int) = 2;
+            } = 1;
+        } = 10;";
+        assert_eq!(
+            check_str(s),
+            vec![
+                "test.x:3:14: type `X` is defined multiple times",
+                "test.x:3:18: constant `A` is defined multiple times",
+                "test.x:7:22: procedure `FUNC` is defined multiple times",
+            ]
+        );
+    }
+
+    #[test]
+    fn check_constants() {
+        let s = "const LEN = 4;
+        struct X { int x[LEN]; int y[SIZE]; };
+        enum E { A = 1, B = LEN, C = 0x1, D = 0x100000000, F = B };
+        program PROG {
+            version VERS {
+                void FUNC(void) = PROC_NUM;
+            } = 1;
+        } = 10;";
+        assert_eq!(
+            check_str(s),
+            vec![
+                "test.x:3:34: value 1 of `C` collides with `A` in enum `E`",
+                "test.x:3:43: value 4294967296 of `D` doesn't fit into an int",
+                "test.x:3:60: value 4 of `F` collides with `B` in enum `E`",
+                "test.x:2:38: unknown constant `SIZE`",
+                "test.x:6:35: unknown constant `PROC_NUM`",
+            ]
+        );
+    }
+
+    #[test]
+    fn check_max_len() {
+        let s = "typedef int Ints<-1>;
+        struct X { int a<LEN>; string b<0x100000000>; opaque c<4>; };
+        program PROG {
+            version VERS {
+                void FUNC(void) = 1;
+            } = 1;
+        } = 10;";
+        assert_eq!(
+            check_str(s),
+            vec![
+                "test.x:1:1: invalid maximum length -1",
+                "test.x:2:26: unknown constant `LEN`",
+                "test.x:2:9: invalid maximum length 4294967296",
+            ]
+        );
+    }
+
+    #[test]
+    fn check_recursion() {
+        let s = "struct A { int x; B b; };
+        struct B { A a[2]; };
+        struct List { int x; List *next; };
+        struct Tree { Tree children<>; };
+        typedef C D;
+        union C switch (int x) { case 0: D d; default: void; };
+        program PROG {
+            version VERS {
+                void FUNC(void) = 1;
+            } = 1;
+        } = 10;";
+        assert_eq!(
+            check_str(s),
+            vec![
+                "test.x:1:8: type `A` contains itself without an optional-data (`*`) indirection",
+                "test.x:2:16: type `B` contains itself without an optional-data (`*`) indirection",
+                "test.x:5:19: type `D` contains itself without an optional-data (`*`) indirection",
+                "test.x:6:15: type `C` contains itself without an optional-data (`*`) indirection",
+            ]
+        );
+    }
+
+    #[test]
+    fn check_union_case_range() {
+        let s = "union U switch (int x) {
+            case 1: int a;
+            case 0x80000000: int b;
+            case 1: int c;
+            case 2: case 3: case 2: void;
+            default: void;
+        };
+        program PROG {
+            version VERS {
+                U FUNC(void) = 1;
+            } = 1;
+        } = 10;";
+        assert_eq!(
+            check_str(s),
+            vec![
+                "test.x:3:18: case 2147483648 of union `U` doesn't fit into the discriminant",
+                "test.x:4:18: case 1 of union `U` is used multiple times",
+                "test.x:5:34: case 2 of union `U` is used multiple times",
+            ]
+        );
+    }
+}
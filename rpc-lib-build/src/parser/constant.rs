@@ -9,12 +9,15 @@
 use crate::parser::Rule;
 
 use proc_macro2::TokenStream;
-use quote::{format_ident, quote};
+use quote::{format_ident, quote, ToTokens};
+
+use super::error::{Error, Span};
 
 #[derive(PartialEq, Debug)]
 pub struct ConstantDeclaration {
-    name: String,
-    value: Value,
+    pub name: String,
+    pub value: Value,
+    pub span: Span,
 }
 
 #[derive(PartialEq, Clone, Debug)]
@@ -31,6 +34,19 @@ impl From<&ConstantDeclaration> for TokenStream {
     }
 }
 
+impl Value {
+    /// The value as a `usize` expression, used for maximum lengths.
+    pub fn to_usize_tokens(&self) -> TokenStream {
+        match self {
+            Value::Numeric { val } => usize::try_from(*val).unwrap().to_token_stream(),
+            Value::Named { name } => {
+                let name = format_ident!("{}", name);
+                quote!(#name as usize)
+            }
+        }
+    }
+}
+
 impl From<&Value> for TokenStream {
     fn from(value: &Value) -> TokenStream {
         match value {
@@ -38,13 +54,17 @@ impl From<&Value> for TokenStream {
                 quote!(#val)
             }
             Value::Named { name } => {
+                let name = format_ident!("{}", name);
                 quote!(#name)
             }
         }
     }
 }
 
-fn parse_num(constant: pest::iterators::Pair<'_, Rule>) -> i64 {
+/// Parses a decimal, hexadecimal (`0x...`) or octal (`0...`) constant that has to fit into `T`.
+pub(crate) fn parse_constant<T: TryFrom<i64>>(
+    constant: &pest::iterators::Pair<'_, Rule>,
+) -> Result<T, Error> {
     let rule_str = constant.as_str();
     if rule_str.len() >= 3 && &rule_str[0..2] == "0x" {
         // Hex
@@ -56,7 +76,20 @@ fn parse_num(constant: pest::iterators::Pair<'_, Rule>) -> i64 {
         // Dec
         rule_str.parse::<i64>()
     }
-    .unwrap()
+    .ok()
+    .and_then(|val| T::try_from(val).ok())
+    .ok_or_else(|| {
+        Error::new(
+            Span::from(constant.as_span()),
+            format!("constant `{}` is out of range", rule_str),
+        )
+        .at_token(rule_str)
+    })
+}
+
+/// Parses a constant that was already checked by [`super::check_constants`].
+fn parse_num(constant: pest::iterators::Pair<'_, Rule>) -> i64 {
+    parse_constant(&constant).expect("constant out of range")
 }
 
 impl From<pest::iterators::Pair<'_, Rule>> for Value {
@@ -76,6 +109,7 @@ impl From<pest::iterators::Pair<'_, Rule>> for Value {
 
 impl From<pest::iterators::Pair<'_, Rule>> for ConstantDeclaration {
     fn from(constant_def: pest::iterators::Pair<'_, Rule>) -> ConstantDeclaration {
+        let span = Span::from(constant_def.as_span());
         let mut it = constant_def.into_inner();
         let name = it.next().unwrap();
         let value = it.next().unwrap();
@@ -84,6 +118,7 @@ impl From<pest::iterators::Pair<'_, Rule>> for ConstantDeclaration {
             value: Value::Numeric {
                 val: parse_num(value),
             },
+            span,
         }
     }
 }
@@ -98,10 +133,12 @@ mod tests {
     fn parse_constant_decimal() {
         // Parsing
         let mut parsed = RPCLParser::parse(Rule::constant_def, "const CON = 23;").unwrap();
-        let const_generated = ConstantDeclaration::from(parsed.next().unwrap());
+        let mut const_generated = ConstantDeclaration::from(parsed.next().unwrap());
+        const_generated.span = Span::default();
         let const_coded = ConstantDeclaration {
             name: "CON".to_string(),
             value: Value::Numeric { val: 23 },
+            span: Span::default(),
         };
         assert!(const_generated == const_coded, "Constant parsing wrong");
 
@@ -113,8 +150,8 @@ mod tests {
         assert!(
             generated_code.to_string() == rust_code.to_string(),
             "DataType: Generated code wrong:\n{}\n{}",
-            generated_code.to_string(),
-            rust_code.to_string()
+            generated_code,
+            rust_code
         );
     }
 
@@ -122,10 +159,12 @@ mod tests {
     fn parse_constant_hexadecimal() {
         // Parsing
         let mut parsed = RPCLParser::parse(Rule::constant_def, "const CON2 = 0x2889;").unwrap();
-        let const_generated = ConstantDeclaration::from(parsed.next().unwrap());
+        let mut const_generated = ConstantDeclaration::from(parsed.next().unwrap());
+        const_generated.span = Span::default();
         let const_coded = ConstantDeclaration {
             name: "CON2".to_string(),
             value: Value::Numeric { val: 0x2889 },
+            span: Span::default(),
         };
         assert!(const_generated == const_coded, "Constant parsing wrong");
 
@@ -137,8 +176,8 @@ mod tests {
         assert!(
             generated_code.to_string() == rust_code.to_string(),
             "DataType: Generated code wrong:\n{}\n{}",
-            generated_code.to_string(),
-            rust_code.to_string()
+            generated_code,
+            rust_code
         );
     }
 
@@ -146,10 +185,12 @@ mod tests {
     fn parse_constant_negative_decimal() {
         // Parsing
         let mut parsed = RPCLParser::parse(Rule::constant_def, "const CON = -68;").unwrap();
-        let const_generated = ConstantDeclaration::from(parsed.next().unwrap());
+        let mut const_generated = ConstantDeclaration::from(parsed.next().unwrap());
+        const_generated.span = Span::default();
         let const_coded = ConstantDeclaration {
             name: "CON".to_string(),
             value: Value::Numeric { val: -68 },
+            span: Span::default(),
         };
         assert!(const_generated == const_coded, "Constant parsing wrong");
 
@@ -161,8 +202,8 @@ mod tests {
         assert!(
             generated_code.to_string() == rust_code.to_string(),
             "DataType: Generated code wrong:\n{}\n{}",
-            generated_code.to_string(),
-            rust_code.to_string()
+            generated_code,
+            rust_code
         );
     }
 
@@ -170,10 +211,12 @@ mod tests {
     fn parse_constant_octal() {
         // Parsing
         let mut parsed = RPCLParser::parse(Rule::constant_def, "const CON = 047;").unwrap();
-        let const_generated = ConstantDeclaration::from(parsed.next().unwrap());
+        let mut const_generated = ConstantDeclaration::from(parsed.next().unwrap());
+        const_generated.span = Span::default();
         let const_coded = ConstantDeclaration {
             name: "CON".to_string(),
             value: Value::Numeric { val: 39 },
+            span: Span::default(),
         };
         assert!(const_generated == const_coded, "Constant parsing wrong");
 
@@ -185,8 +228,8 @@ mod tests {
         assert!(
             generated_code.to_string() == rust_code.to_string(),
             "DataType: Generated code wrong:\n{}\n{}",
-            generated_code.to_string(),
-            rust_code.to_string()
+            generated_code,
+            rust_code
         );
     }
 }
@@ -16,14 +16,31 @@ use super::uniondef::{parse_union_type_spec, Union};
 
 #[derive(PartialEq, Debug, Clone)]
 pub enum DataType {
-    Integer { length: u32, signed: bool },
-    Float { length: u32 },
+    Integer {
+        length: u32,
+        signed: bool,
+    },
+    Float {
+        length: u32,
+    },
     String,
+    /// String mapped to `XdrString`, see [`Specification::map_strings_to_bytes`]
+    ///
+    /// [`Specification::map_strings_to_bytes`]: super::xdr_spec::Specification::map_strings_to_bytes
+    ByteString,
     Boolean,
-    TypeDef { name: String },
-    Struct { def: Struct },
-    Union { def: Union },
-    Enum { def: Enum },
+    TypeDef {
+        name: String,
+    },
+    Struct {
+        def: Struct,
+    },
+    Union {
+        def: Union,
+    },
+    Enum {
+        def: Enum,
+    },
     Void,
 }
 
@@ -32,24 +49,33 @@ impl From<&DataType> for TokenStream {
         match data_type {
             DataType::Integer { length, signed } => match signed {
                 true => match length {
+                    8 => quote!(i8),
+                    16 => quote!(i16),
                     32 => quote!(i32),
                     64 => quote!(i64),
-                    _ => panic!(""),
+                    _ => unreachable!("the grammar only allows 8, 16, 32 and 64 bit integers"),
                 },
                 false => match length {
+                    // `u8` is reserved for opaque data
+                    8 => quote!(rpc_lib::XdrUChar),
+                    16 => quote!(u16),
                     32 => quote!(u32),
                     64 => quote!(u64),
-                    _ => panic!(""),
+                    _ => unreachable!("the grammar only allows 8, 16, 32 and 64 bit integers"),
                 },
             },
             DataType::Float { length } => match length {
                 32 => quote!(f32),
                 64 => quote!(f64),
-                _ => panic!(""),
+                128 => quote!(rpc_lib::Quadruple),
+                _ => unreachable!("the grammar only allows 32, 64 and 128 bit floats"),
             },
             DataType::String => {
                 quote!(String)
             }
+            DataType::ByteString => {
+                quote!(rpc_lib::XdrString)
+            }
             DataType::Boolean => {
                 quote!(bool)
             }
@@ -57,14 +83,8 @@ impl From<&DataType> for TokenStream {
                 let ident = quote::format_ident!("{}", name);
                 quote!(#ident)
             }
-            DataType::Struct { def: _ } => {
-                panic!("Anonymous struct as Datatype not implemented");
-            }
-            DataType::Union { def: _ } => {
-                panic!("Anonymous union as Datatype not implemented");
-            }
-            DataType::Enum { def: _ } => {
-                panic!("Anonymous enum as Datatype not implemented");
+            DataType::Struct { .. } | DataType::Union { .. } | DataType::Enum { .. } => {
+                unreachable!("anonymous types are named by Specification::name_anonymous_types")
             }
             DataType::Void => {
                 quote!()
@@ -74,7 +94,12 @@ impl From<&DataType> for TokenStream {
 }
 
 fn parse_primitive(primitive_type: pest::iterators::Pair<'_, Rule>) -> DataType {
-    match primitive_type.as_str() {
+    // The grammar allows any whitespace between `unsigned` and the type
+    let words = primitive_type
+        .as_str()
+        .split_whitespace()
+        .collect::<Vec<_>>();
+    match words.join(" ").as_str() {
         "unsigned int" => DataType::Integer {
             length: 32,
             signed: false,
@@ -91,12 +116,28 @@ fn parse_primitive(primitive_type: pest::iterators::Pair<'_, Rule>) -> DataType
             length: 64,
             signed: true,
         },
+        "unsigned short" => DataType::Integer {
+            length: 16,
+            signed: false,
+        },
+        "short" => DataType::Integer {
+            length: 16,
+            signed: true,
+        },
+        "unsigned char" => DataType::Integer {
+            length: 8,
+            signed: false,
+        },
+        "char" => DataType::Integer {
+            length: 8,
+            signed: true,
+        },
         "float" => DataType::Float { length: 32 },
         "double" => DataType::Float { length: 64 },
         "quadruple" => DataType::Float { length: 128 },
         "bool" => DataType::Boolean,
         "string" | "string<>" => DataType::String,
-        _ => panic!("Syntax Error"),
+        other => unreachable!("`{}` isn't a primitive_type", other),
     }
 }
 
@@ -119,7 +160,7 @@ impl From<pest::iterators::Pair<'_, Rule>> for DataType {
             Rule::identifier => DataType::TypeDef {
                 name: inner_rule.as_str().to_string(),
             },
-            _ => panic!("Syntax Error"),
+            _ => unreachable!("type_specifier only contains the rules above"),
         }
     }
 }
@@ -148,8 +189,8 @@ mod tests {
         assert!(
             generated_code.to_string() == rust_code.to_string(),
             "DataType: Generated code wrong:\n{}\n{}",
-            generated_code.to_string(),
-            rust_code.to_string()
+            generated_code,
+            rust_code
         );
     }
 
@@ -167,8 +208,8 @@ mod tests {
         assert!(
             generated_code.to_string() == rust_code.to_string(),
             "DataType: Generated code wrong:\n{}\n{}",
-            generated_code.to_string(),
-            rust_code.to_string()
+            generated_code,
+            rust_code
         );
     }
 
@@ -185,11 +226,68 @@ mod tests {
         assert!(
             generated_code.to_string() == rust_code.to_string(),
             "DataType: Generated code wrong:\n{}\n{}",
-            generated_code.to_string(),
-            rust_code.to_string()
+            generated_code,
+            rust_code
         );
     }
 
+    #[test]
+    fn parse_type_spec_small_integers() {
+        for (spec, rust_code) in [
+            ("char", quote!(i8)),
+            ("unsigned char", quote!(rpc_lib::XdrUChar)),
+            ("short", quote!(i16)),
+            ("unsigned short", quote!(u16)),
+        ] {
+            let mut parsed = RPCLParser::parse(Rule::type_specifier, spec).unwrap();
+            let data_generated = DataType::from(parsed.next().unwrap());
+            let generated_code: TokenStream = (&data_generated).into();
+            assert_eq!(
+                generated_code.to_string(),
+                rust_code.to_string(),
+                "{}",
+                spec
+            );
+        }
+        // Identifiers starting with a type name
+        let mut parsed = RPCLParser::parse(Rule::type_specifier, "shorts").unwrap();
+        let data_generated = DataType::from(parsed.next().unwrap());
+        assert_eq!(
+            data_generated,
+            DataType::TypeDef {
+                name: "shorts".into()
+            }
+        );
+    }
+
+    #[test]
+    fn parse_type_spec_unsigned_whitespace() {
+        for spec in ["unsigned\tint", "unsigned  int", "unsigned\n int"] {
+            let mut parsed = RPCLParser::parse(Rule::type_specifier, spec).unwrap();
+            let data_generated = DataType::from(parsed.next().unwrap());
+            assert_eq!(
+                data_generated,
+                DataType::Integer {
+                    length: 32,
+                    signed: false,
+                },
+                "{:?}",
+                spec
+            );
+        }
+    }
+
+    #[test]
+    fn parse_type_spec_quadruple() {
+        let mut parsed = RPCLParser::parse(Rule::type_specifier, "quadruple").unwrap();
+        let data_generated = DataType::from(parsed.next().unwrap());
+        assert_eq!(data_generated, DataType::Float { length: 128 });
+
+        let rust_code: TokenStream = quote!(rpc_lib::Quadruple);
+        let generated_code: TokenStream = (&data_generated).into();
+        assert_eq!(generated_code.to_string(), rust_code.to_string());
+    }
+
     #[test]
     fn parse_type_spec_custom_type_1() {
         // Parsing
@@ -206,13 +304,13 @@ mod tests {
         assert!(
             generated_code.to_string() == rust_code.to_string(),
             "DataType: Generated code wrong:\n{}\n{}",
-            generated_code.to_string(),
-            rust_code.to_string()
+            generated_code,
+            rust_code
         );
     }
 
     #[test]
-    #[should_panic(expected = "Anonymous enum as Datatype not implemented")]
+    #[should_panic(expected = "anonymous types are named")]
     fn parse_enum_type_spec_1() {
         let mut parsed = RPCLParser::parse(Rule::type_specifier, "enum { A = 1 }").unwrap();
         let data_generated = DataType::from(parsed.next().unwrap());
@@ -229,8 +327,8 @@ mod tests {
         assert!(
             generated_code.to_string() == rust_code.to_string(),
             "DataType: Generated code wrong:\n{}\n{}",
-            generated_code.to_string(),
-            rust_code.to_string()
+            generated_code,
+            rust_code
         );
     }
 }
@@ -17,9 +17,13 @@ use super::datatype::DataType;
 #[derive(PartialEq, Debug, Clone)]
 pub enum DeclarationType {
     Optional,
+    /// Chain of optional data, that is mapped to a `Vec`
+    List,
     VarlenArray,
     ArraySlice,
-    FixedlenArray { length: Value },
+    FixedlenArray {
+        length: Value,
+    },
     TypeNameDecl,
     VoidDecl,
 }
@@ -30,6 +34,8 @@ pub struct Declaration {
     pub data_type: DataType, // e.g. int(-array), (optional)char, (varlen-)double
     pub name: String,
     pub needs_lifetime: bool,
+    /// Maximum length of variable-length arrays, opaque data and strings (`<N>`)
+    pub max_len: Option<Value>,
 }
 impl Declaration {
     pub fn update_contains_vararray(&self, typedefs_with_lifetime: &HashSet<String>) -> bool {
@@ -46,11 +52,24 @@ impl Declaration {
         }
     }
 
+    /// Maximum length as a `usize` expression, if one is declared.
+    pub fn max_len_tokens(&self) -> Option<TokenStream> {
+        self.max_len.as_ref().map(Value::to_usize_tokens)
+    }
+
     pub fn to_rust_tokens(&self) -> TokenStream {
         let data_type: TokenStream = (&self.data_type).into();
         match &self.decl_type {
             DeclarationType::Optional => {
-                quote!(std::Option<#data_type>)
+                // User-defined types are boxed, so that they can contain themselves (linked lists)
+                if let DataType::TypeDef { .. } = self.data_type {
+                    quote!(std::option::Option<std::boxed::Box<#data_type>>)
+                } else {
+                    quote!(std::option::Option<#data_type>)
+                }
+            }
+            DeclarationType::List => {
+                quote!(rpc_lib::XdrList<#data_type>)
             }
             DeclarationType::VarlenArray => {
                 quote!(std::vec::Vec<#data_type>)
@@ -61,7 +80,10 @@ impl Declaration {
             DeclarationType::FixedlenArray { length } => {
                 let len = match length {
                     Value::Numeric { val } => usize::try_from(*val).unwrap().to_token_stream(),
-                    Value::Named { name } => name.to_token_stream(),
+                    Value::Named { name } => {
+                        let name = quote::format_ident!("{}", name);
+                        quote!(#name as usize)
+                    }
                 };
                 quote!([#data_type; #len])
             }
@@ -101,6 +123,7 @@ fn parse_optional(pointer: pest::iterators::Pair<'_, Rule>) -> Declaration {
         data_type: DataType::from(optional_type),
         name: optional_name.as_str().to_string(),
         needs_lifetime: false,
+        max_len: None,
     }
 }
 
@@ -108,12 +131,12 @@ fn parse_varlen_array(varlen_array: pest::iterators::Pair<'_, Rule>) -> Declarat
     let mut it = varlen_array.into_inner();
     let varlen_type = it.next().unwrap();
     let varlen_name = it.next().unwrap();
-    // Next may be match rule "value", but because std::Vec is used, minimum length of varlen-array is not important
     Declaration {
         decl_type: DeclarationType::VarlenArray,
         data_type: DataType::from(varlen_type),
         name: varlen_name.as_str().to_string(),
         needs_lifetime: false,
+        max_len: it.next().map(Value::from),
     }
 }
 
@@ -129,6 +152,7 @@ fn parse_fixedlen_array(fixedlen_array: pest::iterators::Pair<'_, Rule>) -> Decl
         data_type: DataType::from(fixedlen_type),
         name: fixedlen_name.as_str().to_string(),
         needs_lifetime: false,
+        max_len: None,
     }
 }
 
@@ -139,6 +163,7 @@ impl From<pest::iterators::Pair<'_, Rule>> for Declaration {
             data_type: DataType::Void,
             name: "".to_string(),
             needs_lifetime: false,
+            max_len: None,
         };
         // declaration > inner_rule (e.g. pointer, string_decl, varlen_array)
         let inner_token = declaration.into_inner().next().unwrap();
@@ -148,13 +173,15 @@ impl From<pest::iterators::Pair<'_, Rule>> for Declaration {
                 return parse_optional(inner_token);
             }
             Rule::string_decl => {
-                // String: name: string_decl > identifier
-                let name = inner_token.into_inner().next().unwrap().as_str();
+                // String: name: string_decl > identifier, value?
+                let mut it = inner_token.into_inner();
+                let name = it.next().unwrap().as_str();
                 return Declaration {
                     decl_type: DeclarationType::TypeNameDecl,
                     data_type: DataType::String,
                     name: name.to_string(),
                     needs_lifetime: false,
+                    max_len: it.next().map(Value::from),
                 };
             }
             Rule::varlen_array => {
@@ -184,6 +211,7 @@ impl From<pest::iterators::Pair<'_, Rule>> for Declaration {
                     data_type: DataType::Void,
                     name: "".to_string(),
                     needs_lifetime: false,
+                    max_len: None,
                 };
             }
             _ => eprintln!("Syntax error"),
@@ -211,6 +239,7 @@ mod tests {
             },
             name: "array".to_string(),
             needs_lifetime: false,
+            max_len: None,
         };
         assert!(decl_generated == decl_coded, "Declaration parsing wrong");
 
@@ -220,8 +249,8 @@ mod tests {
         assert!(
             generated_code.to_string() == rust_code.to_string(),
             "Declaration: Generated code wrong:\n{}\n{}",
-            generated_code.to_string(),
-            rust_code.to_string()
+            generated_code,
+            rust_code
         );
     }
 
@@ -237,6 +266,7 @@ mod tests {
                 signed: true,
             },
             needs_lifetime: false,
+            max_len: None,
             name: "array2_".to_string(),
         };
         assert!(decl_generated == decl_coded, "Declaration parsing wrong");
@@ -247,8 +277,8 @@ mod tests {
         assert!(
             generated_code.to_string() == rust_code.to_string(),
             "Declaration: Generated code wrong:\n{}\n{}",
-            generated_code.to_string(),
-            rust_code.to_string()
+            generated_code,
+            rust_code
         );
     }
 
@@ -263,6 +293,7 @@ mod tests {
                 name: "CustomType".to_string(),
             },
             needs_lifetime: false,
+            max_len: Some(Value::Numeric { val: 2 }),
             name: "_XR234z".to_string(),
         };
         assert!(decl_generated == decl_coded, "Declaration parsing wrong");
@@ -273,8 +304,8 @@ mod tests {
         assert!(
             generated_code.to_string() == rust_code.to_string(),
             "Declaration: Generated code wrong:\n{}\n{}",
-            generated_code.to_string(),
-            rust_code.to_string()
+            generated_code,
+            rust_code
         );
     }
 
@@ -292,6 +323,7 @@ mod tests {
                 signed: true,
             },
             needs_lifetime: false,
+            max_len: None,
             name: "arr".to_string(),
         };
         assert!(decl_generated == decl_coded, "Declaration parsing wrong");
@@ -302,8 +334,8 @@ mod tests {
         assert!(
             generated_code.to_string() == rust_code.to_string(),
             "Declaration: Generated code wrong:\n{}\n{}",
-            generated_code.to_string(),
-            rust_code.to_string()
+            generated_code,
+            rust_code
         );
     }
 
@@ -320,6 +352,7 @@ mod tests {
                 name: "CustomType".to_string(),
             },
             needs_lifetime: false,
+            max_len: None,
             name: "_XR234z".to_string(),
         };
         assert!(decl_generated == decl_coded, "Declaration parsing wrong");
@@ -330,8 +363,8 @@ mod tests {
         assert!(
             generated_code.to_string() == rust_code.to_string(),
             "Declaration: Generated code wrong:\n{}\n{}",
-            generated_code.to_string(),
-            rust_code.to_string()
+            generated_code,
+            rust_code
         );
     }
 
@@ -346,6 +379,7 @@ mod tests {
                 name: "CustomType".to_string(),
             },
             needs_lifetime: false,
+            max_len: None,
             name: "name_23Z".to_string(),
         };
         assert!(decl_generated == decl_coded, "Declaration parsing wrong");
@@ -356,8 +390,8 @@ mod tests {
         assert!(
             generated_code.to_string() == rust_code.to_string(),
             "Declaration: Generated code wrong:\n{}\n{}",
-            generated_code.to_string(),
-            rust_code.to_string()
+            generated_code,
+            rust_code
         );
     }
 
@@ -370,6 +404,7 @@ mod tests {
             decl_type: DeclarationType::TypeNameDecl,
             data_type: DataType::String,
             needs_lifetime: false,
+            max_len: None,
             name: "x".to_string(),
         };
         assert!(decl_generated == decl_coded, "Declaration parsing wrong");
@@ -380,8 +415,8 @@ mod tests {
         assert!(
             generated_code.to_string() == rust_code.to_string(),
             "Declaration: Generated code wrong:\n{}\n{}",
-            generated_code.to_string(),
-            rust_code.to_string()
+            generated_code,
+            rust_code
         );
     }
 
@@ -394,6 +429,7 @@ mod tests {
             decl_type: DeclarationType::TypeNameDecl,
             data_type: DataType::String,
             needs_lifetime: false,
+            max_len: Some(Value::Numeric { val: 24 }),
             name: "_2x".to_string(),
         };
         assert!(decl_generated == decl_coded, "Declaration parsing wrong");
@@ -404,8 +440,8 @@ mod tests {
         assert!(
             generated_code.to_string() == rust_code.to_string(),
             "Declaration: Generated code wrong:\n{}\n{}",
-            generated_code.to_string(),
-            rust_code.to_string()
+            generated_code,
+            rust_code
         );
     }
 
@@ -420,18 +456,20 @@ mod tests {
                 name: "CustomType".to_string(),
             },
             needs_lifetime: false,
+            max_len: None,
             name: "name_23Z".to_string(),
         };
         assert!(decl_generated == decl_coded, "Declaration parsing wrong");
 
         // Code-gen
-        let rust_code: TokenStream = quote!(name_23Z: std::Option<CustomType>);
+        let rust_code: TokenStream =
+            quote!(name_23Z: std::option::Option<std::boxed::Box<CustomType>>);
         let generated_code: TokenStream = (&decl_generated).into();
         assert!(
             generated_code.to_string() == rust_code.to_string(),
             "Declaration: Generated code wrong:\n{}\n{}",
-            generated_code.to_string(),
-            rust_code.to_string()
+            generated_code,
+            rust_code
         );
     }
 
@@ -447,18 +485,19 @@ mod tests {
                 signed: false,
             },
             needs_lifetime: false,
+            max_len: None,
             name: "Optional_2_Int".to_string(),
         };
         assert!(decl_generated == decl_coded, "Declaration parsing wrong");
 
         // Code-gen
-        let rust_code: TokenStream = quote!(Optional_2_Int: std::Option<u64>);
+        let rust_code: TokenStream = quote!(Optional_2_Int: std::option::Option<u64>);
         let generated_code: TokenStream = (&decl_generated).into();
         assert!(
             generated_code.to_string() == rust_code.to_string(),
             "Declaration: Generated code wrong:\n{}\n{}",
-            generated_code.to_string(),
-            rust_code.to_string()
+            generated_code,
+            rust_code
         );
     }
 }
@@ -12,11 +12,13 @@ use proc_macro2::TokenStream;
 use quote::{format_ident, quote};
 
 use super::constant::Value;
+use super::error::Span;
 
 #[derive(PartialEq, Debug)]
 pub struct Enumdef {
     pub name: String,
-    enum_body: Enum,
+    pub enum_body: Enum,
+    pub span: Span,
 }
 
 #[derive(PartialEq, Debug, Clone)]
@@ -28,7 +30,54 @@ impl From<&Enumdef> for TokenStream {
     fn from(enum_def: &Enumdef) -> TokenStream {
         let name = quote::format_ident!("{}", enum_def.name);
         let enum_body = TokenStream::from(&enum_def.enum_body);
-        quote!(enum #name #enum_body)
+        let cases: Vec<_> = enum_def
+            .enum_body
+            .cases
+            .iter()
+            .map(|(case_ident, _)| format_ident!("{}", case_ident))
+            .collect();
+        let error = format!("invalid value {{}} of enum {}", enum_def.name);
+        quote! {
+            #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+            #[repr(i32)]
+            enum #name #enum_body
+
+            impl std::convert::TryFrom<i32> for #name {
+                type Error = std::io::Error;
+
+                fn try_from(value: i32) -> std::io::Result<Self> {
+                    #(if value == Self::#cases as i32 {
+                        return Ok(Self::#cases);
+                    })*
+                    Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!(#error, value),
+                    ))
+                }
+            }
+
+            impl From<#name> for i32 {
+                fn from(value: #name) -> i32 {
+                    value as i32
+                }
+            }
+
+            impl XdrSerialize for #name {
+                fn len(&self) -> usize {
+                    XdrSerialize::len(&(*self as i32))
+                }
+
+                fn serialize(&self, writer: impl std::io::Write) -> std::io::Result<()> {
+                    XdrSerialize::serialize(&(*self as i32), writer)
+                }
+            }
+
+            impl XdrDeserialize for #name {
+                fn deserialize(reader: impl std::io::Read) -> std::io::Result<Self> {
+                    std::convert::TryFrom::try_from(i32::deserialize(reader)?)
+                }
+            }
+        }
     }
 }
 
@@ -39,11 +88,15 @@ impl From<&Enum> for TokenStream {
             let case_name = format_ident!("{}", case_ident);
             match case_value {
                 Value::Numeric { val } => {
-                    code = quote!(#code #case_name = #val as isize,);
+                    code = quote!(#code #case_name = #val as i32,);
+                }
+                Value::Named { name } if en.cases.iter().any(|(case, _)| case == name) => {
+                    let value_name = format_ident!("{}", name);
+                    code = quote!(#code #case_name = Self::#value_name as i32,);
                 }
                 Value::Named { name } => {
                     let value_name = format_ident!("{}", name);
-                    code = quote!(#code #case_name = #value_name,);
+                    code = quote!(#code #case_name = #value_name as i32,);
                 }
             }
         }
@@ -57,6 +110,7 @@ pub fn parse_enum_type_spec(enum_type_spec: pest::iterators::Pair<'_, Rule>) ->
 
 impl From<pest::iterators::Pair<'_, Rule>> for Enumdef {
     fn from(enum_def: pest::iterators::Pair<'_, Rule>) -> Enumdef {
+        let span = Span::from(enum_def.as_span());
         let mut iter = enum_def.into_inner();
         let enum_name = iter.next().unwrap();
         let enum_body = iter.next().unwrap();
@@ -64,6 +118,7 @@ impl From<pest::iterators::Pair<'_, Rule>> for Enumdef {
         Enumdef {
             name: enum_name.as_str().to_string(),
             enum_body: Enum::from(enum_body),
+            span,
         }
     }
 }
@@ -92,8 +147,11 @@ mod tests {
     #[test]
     fn parse_enum_1() {
         // Parsing
-        let mut parsed =
-            RPCLParser::parse(Rule::enum_body, "{CASE1 = 2, CASE_T = 0xa, _CASE = CONST}").unwrap();
+        let mut parsed = RPCLParser::parse(
+            Rule::enum_body,
+            "{CASE1 = 2, CASE_T = 0xa, _CASE = CONST, C = CASE1}",
+        )
+        .unwrap();
         let enum_generated = Enum::from(parsed.next().unwrap());
         let enum_coded = Enum {
             cases: vec![
@@ -105,19 +163,29 @@ mod tests {
                         name: "CONST".into(),
                     },
                 ),
+                (
+                    "C".into(),
+                    Value::Named {
+                        name: "CASE1".into(),
+                    },
+                ),
             ],
         };
         assert!(enum_generated == enum_coded, "Enum parsing wrong");
 
         // Code-gen
-        let rust_code: TokenStream =
-            quote!( { CASE1 = 2i64 as isize, CASE_T = 10i64 as isize, _CASE = CONST, } );
+        let rust_code: TokenStream = quote!({
+            CASE1 = 2i64 as i32,
+            CASE_T = 10i64 as i32,
+            _CASE = CONST as i32,
+            C = Self::CASE1 as i32,
+        });
         let generated_code: TokenStream = (&enum_generated).into();
         assert!(
             generated_code.to_string() == rust_code.to_string(),
             "DataType: Generated code wrong:\n{}\n{}",
-            generated_code.to_string(),
-            rust_code.to_string()
+            generated_code,
+            rust_code
         );
     }
 
@@ -125,7 +193,8 @@ mod tests {
     fn parse_enum_def_1() {
         // Parsing
         let mut parsed = RPCLParser::parse(Rule::enum_def, "enum Name { A = 1, B = 2};").unwrap();
-        let enum_generated = Enumdef::from(parsed.next().unwrap());
+        let mut enum_generated = Enumdef::from(parsed.next().unwrap());
+        enum_generated.span = Span::default();
         let enum_coded = Enumdef {
             name: "Name".to_string(),
             enum_body: Enum {
@@ -134,22 +203,64 @@ mod tests {
                     ("B".into(), Value::Numeric { val: 2 }),
                 ],
             },
+            span: Span::default(),
         };
         assert!(enum_generated == enum_coded, "Enum parsing wrong");
 
         // Code-gen
-        let rust_code: TokenStream = quote!(
+        let rust_code: TokenStream = quote! {
+            #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+            #[repr(i32)]
             enum Name {
-                A = 1i64 as isize,
-                B = 2i64 as isize,
+                A = 1i64 as i32,
+                B = 2i64 as i32,
             }
-        );
+
+            impl std::convert::TryFrom<i32> for Name {
+                type Error = std::io::Error;
+
+                fn try_from(value: i32) -> std::io::Result<Self> {
+                    if value == Self::A as i32 {
+                        return Ok(Self::A);
+                    }
+                    if value == Self::B as i32 {
+                        return Ok(Self::B);
+                    }
+                    Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("invalid value {} of enum Name", value),
+                    ))
+                }
+            }
+
+            impl From<Name> for i32 {
+                fn from(value: Name) -> i32 {
+                    value as i32
+                }
+            }
+
+            impl XdrSerialize for Name {
+                fn len(&self) -> usize {
+                    XdrSerialize::len(&(*self as i32))
+                }
+
+                fn serialize(&self, writer: impl std::io::Write) -> std::io::Result<()> {
+                    XdrSerialize::serialize(&(*self as i32), writer)
+                }
+            }
+
+            impl XdrDeserialize for Name {
+                fn deserialize(reader: impl std::io::Read) -> std::io::Result<Self> {
+                    std::convert::TryFrom::try_from(i32::deserialize(reader)?)
+                }
+            }
+        };
         let generated_code: TokenStream = (&enum_generated).into();
         assert!(
             generated_code.to_string() == rust_code.to_string(),
             "DataType: Generated code wrong:\n{}\n{}",
-            generated_code.to_string(),
-            rust_code.to_string()
+            generated_code,
+            rust_code
         );
     }
 
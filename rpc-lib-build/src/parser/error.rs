@@ -0,0 +1,172 @@
+// Copyright 2022 Philipp Fensch
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::parser::Rule;
+use proc_macro2::TokenStream;
+use quote::quote_spanned;
+
+/// Byte range of a definition in the .x-File.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl From<pest::Span<'_>> for Span {
+    fn from(span: pest::Span<'_>) -> Span {
+        Span {
+            start: span.start(),
+            end: span.end(),
+        }
+    }
+}
+
+/// Error in a .x-File, either a syntax error or a semantic error found by the checks after parsing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error {
+    span: Span,
+    token: Option<String>,
+    message: String,
+}
+
+impl Error {
+    pub(crate) fn new(span: Span, message: impl Into<String>) -> Error {
+        Error {
+            span,
+            token: None,
+            message: message.into(),
+        }
+    }
+
+    /// Points the error at the first occurrence of `token` inside of the span.
+    pub(crate) fn at_token(mut self, token: impl Into<String>) -> Error {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Returns line and column (both starting at 1) of the error in `source`.
+    pub fn line_col(&self, source: &str) -> (usize, usize) {
+        let offset = self
+            .token
+            .as_deref()
+            .and_then(|token| find_token(source, self.span, token))
+            .unwrap_or(self.span.start);
+        match pest::Position::new(source, offset) {
+            Some(pos) => pos.line_col(),
+            None => (1, 1),
+        }
+    }
+
+    /// Formats the error as `<path>:<line>:<column>: <message>`.
+    pub fn display(&self, path: &str, source: &str) -> String {
+        let (line, col) = self.line_col(source);
+        format!("{}:{}:{}: {}", path, line, col, self.message)
+    }
+
+    /// Emits the error as `compile_error!` at `span`, used by `include_rpcl`.
+    pub fn to_compile_error(
+        &self,
+        path: &str,
+        source: &str,
+        span: proc_macro2::Span,
+    ) -> TokenStream {
+        let message = self.display(path, source);
+        quote_spanned!(span=> compile_error!(#message);)
+    }
+}
+
+impl From<pest::error::Error<Rule>> for Error {
+    fn from(err: pest::error::Error<Rule>) -> Error {
+        let (start, end) = match err.location {
+            pest::error::InputLocation::Pos(pos) => (pos, pos),
+            pest::error::InputLocation::Span(span) => span,
+        };
+        let col = match err.line_col {
+            pest::error::LineColLocation::Pos((_, col)) => col,
+            pest::error::LineColLocation::Span((_, col), _) => col,
+        };
+        let found = match offending_token(err.line(), col) {
+            Some(token) => format!("`{}`", token),
+            None => "end of line".to_string(),
+        };
+        Error {
+            span: Span { start, end },
+            token: None,
+            message: format!("{}, found {}", err.variant.message(), found),
+        }
+    }
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Returns the token starting at column `col` of `line`.
+fn offending_token(line: &str, col: usize) -> Option<&str> {
+    let (start, first) = line.char_indices().nth(col.saturating_sub(1))?;
+    if first.is_whitespace() {
+        return None;
+    }
+    let rest = &line[start..];
+    let len = if is_ident_char(first) {
+        rest.find(|c: char| !is_ident_char(c)).unwrap_or(rest.len())
+    } else {
+        first.len_utf8()
+    };
+    Some(&rest[..len])
+}
+
+/// Finds `token` as a whole word inside of `span`.
+fn find_token(source: &str, span: Span, token: &str) -> Option<usize> {
+    let text = source.get(span.start..span.end)?;
+    let mut search_start = 0;
+    while let Some(found) = text[search_start..].find(token) {
+        let start = search_start + found;
+        let end = start + token.len();
+        let before = text[..start].chars().next_back();
+        let after = text[end..].chars().next();
+        if !before.is_some_and(is_ident_char) && !after.is_some_and(is_ident_char) {
+            return Some(span.start + start);
+        }
+        search_start = end;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::RPCLParser;
+    use pest::Parser;
+
+    #[test]
+    fn syntax_error_position() {
+        let source = "struct X {\n    int x;\n    int 5y;\n};";
+        let err = Error::from(RPCLParser::parse(Rule::struct_def, source).unwrap_err());
+        assert_eq!(err.line_col(source), (3, 9));
+        assert_eq!(
+            err.display("test.x", source),
+            "test.x:3:9: expected identifier, found `5y`"
+        );
+    }
+
+    #[test]
+    fn token_position() {
+        let source = "struct X {\n    int x;\n    Xy y;\n    X z;\n};";
+        let span = Span {
+            start: 0,
+            end: source.len(),
+        };
+        let err = Error::new(span, "unknown type `X`").at_token("X");
+        assert_eq!(err.line_col(source), (1, 8));
+        let err = Error::new(span, "unknown type `Xy`").at_token("Xy");
+        assert_eq!(err.line_col(source), (3, 5));
+        let err = Error::new(span, "unknown type `Z`").at_token("Z");
+        assert_eq!(err.line_col(source), (1, 1));
+    }
+}
@@ -0,0 +1,216 @@
+// Copyright 2022 Philipp Fensch
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+mod check;
+mod constant;
+mod datatype;
+mod declaration;
+mod enumdef;
+mod error;
+mod procedure;
+mod program;
+mod server;
+mod structdef;
+mod typedef;
+mod uniondef;
+mod xdr_spec;
+
+use pest::Parser;
+use proc_macro2::TokenStream;
+use quote::quote;
+
+pub use error::Error;
+pub use program::Program;
+pub use server::{generate_sample_main, generate_server, generate_stubs};
+pub use xdr_spec::Specification;
+
+#[derive(pest_derive::Parser)]
+#[grammar = "rpcl.pest"]
+pub struct RPCLParser;
+
+/// Parses the contents of a .x-File into its specification and program.
+fn parse_file(x_file: &str) -> Result<(Specification, Program), Error> {
+    let parsed = RPCLParser::parse(Rule::file, x_file)?;
+    check_constants(parsed.clone())?;
+
+    let mut spec = None;
+    let mut program = None;
+    for token in parsed {
+        match token.as_rule() {
+            Rule::specification => {
+                if spec.is_some() {
+                    unimplemented!("Separate spec sections are unimplemented. One would have to merge the two datastructs here...");
+                }
+                spec = Some(Specification::from(token));
+            }
+            Rule::program_def => {
+                program = Some(Program::from(token));
+            }
+            _ => {}
+        }
+    }
+
+    let mut program = program.expect("rpcl file without program is invalid");
+    let mut spec = spec.unwrap_or_default();
+    spec.name_anonymous_types(&mut program);
+    Ok((spec, program))
+}
+
+/// Checks that all constants fit into an `i64`, and program and version numbers into an `u32`,
+/// so that converting the parsed tokens can't fail.
+fn check_constants(parsed: pest::iterators::Pairs<'_, Rule>) -> Result<(), Error> {
+    for token in parsed.flatten() {
+        match token.as_rule() {
+            Rule::constant => {
+                constant::parse_constant::<i64>(&token)?;
+            }
+            Rule::program_def | Rule::version_def => {
+                let number = token.into_inner().last().unwrap();
+                constant::parse_constant::<u32>(&number)?;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Options for mapping the datatypes of a .x-File to Rust
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Options {
+    /// Map linked lists to `rpc_lib::XdrList`s
+    pub lists_as_vec: bool,
+    /// Map strings to `rpc_lib::XdrString`
+    pub strings_as_bytes: bool,
+}
+
+/// Parses and checks the contents of a .x-File.
+pub fn parse(x_file: &str, options: Options) -> Result<(Specification, Program), Vec<Error>> {
+    let (mut spec, mut program) = parse_file(x_file).map_err(|err| vec![err])?;
+    let errors = check::check(&spec, &program);
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    if options.lists_as_vec {
+        spec.map_linked_lists_to_vec(&program);
+    }
+    if options.strings_as_bytes {
+        spec.map_strings_to_bytes(&mut program);
+    }
+    spec.apply_typedef_max_lens(&mut program);
+    spec.update_contains_vararray();
+    Ok((spec, program))
+}
+
+/// Generates the datatypes of `spec` and the procedures of `program` as associated functions of
+/// `struct_name`.
+pub fn generate(spec: &Specification, program: Program, struct_name: &str) -> TokenStream {
+    let types_code = generate_types(spec);
+    let client_code = generate_client(spec, program, struct_name);
+    quote! {
+        #types_code
+        #client_code
+    }
+}
+
+/// Generates the datatypes of `spec` including their XDR-implementations.
+pub fn generate_types(spec: &Specification) -> TokenStream {
+    let spec_code = TokenStream::from(spec);
+    quote! {
+        #spec_code
+        use rpc_lib::{XdrDeserialize, XdrSerialize};
+    }
+}
+
+/// Generates the procedures of `program` as associated functions of `struct_name`.
+pub fn generate_client(
+    spec: &Specification,
+    mut program: Program,
+    struct_name: &str,
+) -> TokenStream {
+    let s_name = quote::format_ident!("{}", struct_name);
+
+    program
+        .versions
+        .iter_mut()
+        .for_each(|v| v.create_sliced_variants(spec));
+
+    let proc_code = TokenStream::from(&program);
+    quote! {
+        impl #s_name {
+            #proc_code
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_rule() {
+        let file_str = "program PROG {
+            version VERS {
+                int FUNC(void) = 1;
+            } = 1;
+        } = 10;";
+        let _parsed = RPCLParser::parse(Rule::file, file_str).expect("Syntax Error in .x-File");
+    }
+
+    #[test]
+    fn test_file_rule_2() {
+        let file_str = "struct X {
+            int x;
+            int y;
+        };
+        
+        program PROG {
+            version VERS {
+                int FUNC(void) = 1;
+            } = 1;
+        } = 10;";
+        let _parsed = RPCLParser::parse(Rule::file, file_str).expect("Syntax Error in .x-File");
+    }
+
+    #[test]
+    fn program_number_radix() {
+        let file_str = "program PROG {
+            version VERS {
+                int FUNC(void) = 1;
+            } = 010;
+        } = 0x20000001;";
+        let (_, program) = parse_file(file_str).unwrap();
+        assert_eq!(program.program_number, 0x2000_0001);
+        assert_eq!(program.versions[0].version_number, 8);
+    }
+
+    #[test]
+    fn constants_out_of_range() {
+        for (file_str, token) in [
+            (
+                "program PROG { version VERS { int FUNC(void) = 1; } = 1; } = 0x100000000;",
+                "0x100000000",
+            ),
+            (
+                "program PROG { version VERS { int FUNC(void) = 1; } = -1; } = 1;",
+                "-1",
+            ),
+            (
+                "const BIG = 0x10000000000000000;
+                program PROG { version VERS { int FUNC(void) = 1; } = 1; } = 1;",
+                "0x10000000000000000",
+            ),
+        ] {
+            let err = parse_file(file_str).unwrap_err();
+            let col = file_str.find(token).unwrap() + 1;
+            assert_eq!(
+                err.display("test.x", file_str),
+                format!("test.x:1:{}: constant `{}` is out of range", col, token)
+            );
+        }
+    }
+}
@@ -12,6 +12,7 @@ use quote::{format_ident, quote};
 
 use super::constant::Value;
 use super::datatype::DataType;
+use super::error::Span;
 
 #[derive(PartialEq, Debug, Clone, Copy)]
 pub enum RawCallType {
@@ -25,8 +26,12 @@ pub struct Procedure {
     pub return_type: DataType,
     // bool stands for mutability
     pub args: std::vec::Vec<DataType>,
+    /// Maximum lengths of the arguments and the result, inherited from their typedefs
+    pub arg_max_lens: std::vec::Vec<Option<Value>>,
+    pub return_max_len: Option<Value>,
     pub num: Value,
     pub slice_call_target_type: Option<RawCallType>,
+    pub span: Span,
 }
 
 impl From<&Procedure> for TokenStream {
@@ -46,16 +51,23 @@ impl From<&Procedure> for TokenStream {
             })
             .collect::<TokenStream>();
 
-        let arg = if !proc.args.is_empty() {
+        let (args_def, arg) = if !proc.args.is_empty() {
             let field_defs = proc
                 .args
                 .iter()
+                .zip(&proc.arg_max_lens)
                 .enumerate()
-                .map(|(i, ty)| {
+                .map(|(i, (ty, max_len))| {
                     let ty = TokenStream::from(ty);
                     let ident = format_ident!("x{}", i);
-                    quote! {
-                        #ident: &'a #ty,
+                    match max_len.as_ref().map(Value::to_usize_tokens) {
+                        Some(max_len) => quote! {
+                            #[xdr(max_len = #max_len)]
+                            #ident: &'a #ty,
+                        },
+                        None => quote! {
+                            #ident: &'a #ty,
+                        },
                     }
                 })
                 .collect::<TokenStream>();
@@ -72,20 +84,21 @@ impl From<&Procedure> for TokenStream {
                 })
                 .collect::<TokenStream>();
 
-            quote! {
-                {
-                    #[derive(::rpc_lib::XdrSerialize)]
-                    struct Args<'a> {
-                        #field_defs
-                    }
-
-                    &Args {
-                        #field_idents
-                    }
+            // A local rather than a block expression, since edition 2024 drops temporaries in the
+            // tail of a block at its end
+            let args_def = quote! {
+                #[derive(::rpc_lib::XdrSerialize)]
+                struct Args<'a> {
+                    #field_defs
                 }
-            }
+
+                let args = Args {
+                    #field_idents
+                };
+            };
+            (args_def, quote!(&args))
         } else {
-            quote!(())
+            (quote!(), quote!(()))
         };
 
         let proc_num = TokenStream::from(&proc.num);
@@ -93,6 +106,7 @@ impl From<&Procedure> for TokenStream {
             match slice_target {
                 RawCallType::UnionI32 => {
                     quote! { fn #proc_name <'a> (&mut self, target: &'a mut rpc_lib::RawResponseUnion<'a, i32>, #arg_defs ) -> std::io::Result<()> {
+                        #args_def
                         self.client.call_with_raw_union_response(#proc_num as u32, #arg, target)
                     }}
                 }
@@ -102,8 +116,13 @@ impl From<&Procedure> for TokenStream {
             quote! { fn #proc_name(&self, #arg_defs) {}}
         } else {
             let return_type = TokenStream::from(&proc.return_type);
+            let call = match proc.return_max_len.as_ref().map(Value::to_usize_tokens) {
+                Some(max_len) => quote!(self.client.call_max_len(#proc_num as u32, #arg, #max_len)),
+                None => quote!(self.client.call(#proc_num as u32, #arg)),
+            };
             quote! { fn #proc_name(&mut self, #arg_defs) -> std::io::Result<#return_type> {
-                self.client.call(#proc_num as u32, #arg)
+                #args_def
+                #call
             }}
         }
     }
@@ -111,6 +130,7 @@ impl From<&Procedure> for TokenStream {
 
 impl From<pest::iterators::Pair<'_, Rule>> for Procedure {
     fn from(procedure_def: pest::iterators::Pair<'_, Rule>) -> Procedure {
+        let span = Span::from(procedure_def.as_span());
         let mut iter = procedure_def.into_inner();
         let proc_return = iter.next().unwrap();
         let proc_name = iter.next().unwrap();
@@ -127,9 +147,12 @@ impl From<pest::iterators::Pair<'_, Rule>> for Procedure {
         Procedure {
             name: proc_name.as_str().to_string(),
             return_type: DataType::from(proc_return.into_inner().next().unwrap()),
+            arg_max_lens: vec![None; arg_vec.len()],
+            return_max_len: None,
             args: arg_vec,
             num: Value::from(proc_num),
             slice_call_target_type: None,
+            span,
         }
     }
 }
@@ -145,7 +168,8 @@ mod tests {
         // Parsing
         let mut parsed =
             RPCLParser::parse(Rule::procedure_def, "float PROC_NAME(int, float) = 1;").unwrap();
-        let proc_generated = Procedure::from(parsed.next().unwrap());
+        let mut proc_generated = Procedure::from(parsed.next().unwrap());
+        proc_generated.span = Span::default();
         let proc_coded = Procedure {
             name: "PROC_NAME".to_string(),
             return_type: DataType::Float { length: 32 },
@@ -156,34 +180,36 @@ mod tests {
                 },
                 DataType::Float { length: 32 },
             ],
+            arg_max_lens: vec![None, None],
+            return_max_len: None,
             num: Value::Numeric { val: 1 },
             slice_call_target_type: None,
+            span: Span::default(),
         };
         assert!(proc_generated == proc_coded, "Procedure parsing wrong");
 
         // Code-gen
         let rust_code: TokenStream = quote! {
             fn PROC_NAME(&mut self, x0: &i32, x1: &f32, ) -> std::io::Result<f32> {
-                self.client.call(1i64 as u32, {
-                    #[derive(::rpc_lib::XdrSerialize)]
-                    struct Args<'a> {
-                        x0: &'a i32,
-                        x1: &'a f32,
-                    }
+                #[derive(::rpc_lib::XdrSerialize)]
+                struct Args<'a> {
+                    x0: &'a i32,
+                    x1: &'a f32,
+                }
 
-                    &Args {
-                        x0,
-                        x1,
-                    }
-                })
+                let args = Args {
+                    x0,
+                    x1,
+                };
+                self.client.call(1i64 as u32, &args)
             }
         };
         let generated_code: TokenStream = (&proc_generated).into();
         assert!(
             generated_code.to_string() == rust_code.to_string(),
             "Procedure: Generated code wrong:\n{}\n{}",
-            generated_code.to_string(),
-            rust_code.to_string()
+            generated_code,
+            rust_code
         );
     }
 
@@ -191,13 +217,17 @@ mod tests {
     fn parse_procedure_2() {
         let mut parsed =
             RPCLParser::parse(Rule::procedure_def, "void PROC_NAME(void) = 0x24;").unwrap();
-        let proc_generated = Procedure::from(parsed.next().unwrap());
+        let mut proc_generated = Procedure::from(parsed.next().unwrap());
+        proc_generated.span = Span::default();
         let proc_coded = Procedure {
             name: "PROC_NAME".to_string(),
             return_type: DataType::Void,
             args: vec![],
+            arg_max_lens: vec![],
+            return_max_len: None,
             num: Value::Numeric { val: 36 },
             slice_call_target_type: None,
+            span: Span::default(),
         };
         assert!(proc_generated == proc_coded, "Procedure parsing wrong");
 
@@ -209,8 +239,8 @@ mod tests {
         assert!(
             generated_code.to_string() == rust_code.to_string(),
             "Procedure: Generated code wrong:\n{}\n{}",
-            generated_code.to_string(),
-            rust_code.to_string()
+            generated_code,
+            rust_code
         );
     }
 }
@@ -11,15 +11,19 @@ use crate::parser::Rule;
 use proc_macro2::TokenStream;
 use quote::quote;
 
+use super::constant::parse_constant;
 use super::datatype::DataType;
+use super::error::Span;
 use super::procedure::{Procedure, RawCallType};
 use super::uniondef::DiscriminantType;
 use super::xdr_spec::Specification;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Program {
+    pub name: String,
     pub program_number: u32,
     pub versions: std::vec::Vec<Version>,
+    pub span: Span,
 }
 
 impl From<&Program> for TokenStream {
@@ -40,8 +44,10 @@ impl From<&Program> for TokenStream {
 impl From<pest::iterators::Pair<'_, Rule>> for Program {
     fn from(program_def: pest::iterators::Pair<'_, Rule>) -> Program {
         let mut prog = Program {
+            name: String::new(),
             program_number: 0,
             versions: std::vec::Vec::new(),
+            span: Span::from(program_def.as_span()),
         };
         let iter = program_def.into_inner();
         for x in iter {
@@ -52,10 +58,11 @@ impl From<pest::iterators::Pair<'_, Rule>> for Program {
                 }
                 Rule::identifier => {
                     // Name of program
+                    prog.name = x.as_str().to_string();
                 }
                 Rule::constant => {
                     // Number of program
-                    prog.program_number = x.as_str().parse::<u32>().unwrap();
+                    prog.program_number = parse_constant(&x).expect("program number out of range");
                 }
                 _ => panic!("Invalid Syntax in Function"),
             }
@@ -64,10 +71,11 @@ impl From<pest::iterators::Pair<'_, Rule>> for Program {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Version {
     pub version_number: u32,
-    procedures: std::vec::Vec<Procedure>,
+    pub procedures: std::vec::Vec<Procedure>,
+    pub span: Span,
 }
 impl Version {
     pub fn create_sliced_variants(&mut self, spec: &Specification) {
@@ -76,15 +84,15 @@ impl Version {
             #[allow(clippy::single_match)]
             match &p.return_type {
                 DataType::TypeDef { name } => match spec.get_type_specification(name) {
-                    Some(ResolvedType::Union(u)) => {
-                        if u.contains_vararray && u.union_body.discriminant == DiscriminantType::Int
-                        {
-                            let mut sliced_proc = p.clone();
-                            sliced_proc.name.push_str("_raw");
-                            sliced_proc.slice_call_target_type = Some(RawCallType::UnionI32);
-                            sliced_proc.return_type = DataType::Void;
-                            sliced_procedures.push(sliced_proc);
-                        }
+                    Some(ResolvedType::Union(u))
+                        if u.contains_vararray
+                            && u.union_body.discriminant == DiscriminantType::Int =>
+                    {
+                        let mut sliced_proc = p.clone();
+                        sliced_proc.name.push_str("_raw");
+                        sliced_proc.slice_call_target_type = Some(RawCallType::UnionI32);
+                        sliced_proc.return_type = DataType::Void;
+                        sliced_procedures.push(sliced_proc);
                     }
                     // sliced variants for structs or enums are not yet supported
                     _ => {}
@@ -113,6 +121,7 @@ impl From<pest::iterators::Pair<'_, Rule>> for Version {
         let mut vers = Version {
             version_number: 0,
             procedures: std::vec::Vec::new(),
+            span: Span::from(version_def.as_span()),
         };
         let iter = version_def.into_inner();
         for x in iter {
@@ -126,7 +135,7 @@ impl From<pest::iterators::Pair<'_, Rule>> for Version {
                 }
                 Rule::constant => {
                     // Number of program
-                    vers.version_number = x.as_str().parse::<u32>().unwrap();
+                    vers.version_number = parse_constant(&x).expect("version number out of range");
                 }
                 _ => panic!("Invalid Syntax in Function"),
             }
@@ -152,6 +161,7 @@ mod tests {
         let mut parsed = RPCLParser::parse(Rule::program_def, s).unwrap();
         let prog = Program::from(parsed.next().unwrap());
 
+        assert!(prog.name == "PROG", "Program Name wrong");
         assert!(prog.program_number == 1, "Program Number wrong");
         assert!(prog.versions.len() == 1, "Number of parsed Versions wrong!");
     }
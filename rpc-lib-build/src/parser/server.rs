@@ -0,0 +1,307 @@
+// Copyright 2022 Philipp Fensch
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+
+use super::datatype::DataType;
+use super::procedure::Procedure;
+use super::program::Program;
+
+fn procedures(program: &Program) -> impl Iterator<Item = &Procedure> {
+    program.versions.iter().flat_map(|v| v.procedures.iter())
+}
+
+fn arg_idents(proc: &Procedure, prefix: &str) -> Vec<proc_macro2::Ident> {
+    (0..proc.args.len())
+        .map(|i| format_ident!("{}x{}", prefix, i))
+        .collect()
+}
+
+/// Signature of the server-side function of a procedure. Arguments are passed by value.
+fn server_signature(proc: &Procedure, prefix: &str) -> TokenStream {
+    let proc_name = format_ident!("{}", proc.name);
+    let idents = arg_idents(proc, prefix);
+    let types = proc.args.iter().map(TokenStream::from);
+    if proc.return_type == DataType::Void {
+        quote!(fn #proc_name(&mut self #(, #idents: #types)*))
+    } else {
+        let return_type = TokenStream::from(&proc.return_type);
+        quote!(fn #proc_name(&mut self #(, #idents: #types)*) -> #return_type)
+    }
+}
+
+/// Generates a trait with a function for every procedure of `program`, mirroring `rpcgen -m`.
+///
+/// The provided `dispatch` function decodes the arguments of a call, calls the procedure and
+/// encodes its result. It is independent of the transport, so that it can be used with any server
+/// loop.
+pub fn generate_server(program: &Program, trait_name: &str) -> TokenStream {
+    let trait_name = format_ident!("{}", trait_name);
+    let signatures = procedures(program).map(|proc| server_signature(proc, ""));
+    let dispatch_arms = procedures(program).map(|proc| {
+        let proc_name = format_ident!("{}", proc.name);
+        let proc_num = TokenStream::from(&proc.num);
+        let idents = arg_idents(proc, "");
+        let types = proc.args.iter().map(TokenStream::from);
+        let call = if proc.return_type == DataType::Void {
+            quote!(self.#proc_name(#(#idents),*);)
+        } else {
+            quote! {
+                let result = self.#proc_name(#(#idents),*);
+                ::rpc_lib::XdrSerialize::serialize(&result, &mut reply)?;
+            }
+        };
+        quote! {
+            if procedure == #proc_num as u32 {
+                #(let #idents = <#types as ::rpc_lib::XdrDeserialize>::deserialize(&mut args)?;)*
+                #call
+                return Ok(true);
+            }
+        }
+    });
+    quote! {
+        /// Server-side functions of the procedures.
+        trait #trait_name {
+            #(#signatures;)*
+
+            /// Decodes the arguments of `procedure` from `args`, calls it and encodes the result
+            /// into `reply`.
+            ///
+            /// Returns `false` if the program doesn't have a procedure with this number. Procedure
+            /// 0 (NULLPROC) is answered with an empty reply, unless the program defines it.
+            #[allow(unused_mut, unused_variables)]
+            fn dispatch(
+                &mut self,
+                procedure: u32,
+                mut args: impl std::io::Read,
+                mut reply: impl std::io::Write,
+            ) -> std::io::Result<bool> {
+                #(#dispatch_arms)*
+                Ok(procedure == 0)
+            }
+        }
+    }
+}
+
+/// Generates an implementation of the server trait, which has to be filled in, mirroring
+/// `rpcgen -Ss`.
+pub fn generate_stubs(program: &Program, trait_name: &str, struct_name: &str) -> TokenStream {
+    let trait_name = format_ident!("{}", trait_name);
+    let struct_name = format_ident!("{}", struct_name);
+    let functions = procedures(program).map(|proc| {
+        let signature = server_signature(proc, "_");
+        let message = format!("insert server code for {}", proc.name);
+        quote! {
+            #signature {
+                todo!(#message)
+            }
+        }
+    });
+    quote! {
+        struct #struct_name;
+
+        impl #trait_name for #struct_name {
+            #(#functions)*
+        }
+    }
+}
+
+/// Generates a `main` function, which connects to the host given on the command line, and a
+/// function calling each procedure and printing the result, mirroring `rpcgen -Sc`. Procedures
+/// without arguments are called from `main`.
+///
+/// Procedures returning `void` are left out, as the client doesn't call them.
+///
+/// The types and the client are expected in the module `module`.
+pub fn generate_sample_main(program: &Program, module: &str, client_name: &str) -> TokenStream {
+    let module = format_ident!("{}", module);
+    let client_name = format_ident!("{}", client_name);
+    let called = || procedures(program).filter(|proc| proc.return_type != DataType::Void);
+    let call_fns = called().map(|proc| {
+        let proc_name = format_ident!("{}", proc.name);
+        let fn_name = format_ident!("call_{}", proc.name.to_lowercase());
+        let idents = arg_idents(proc, "");
+        let types = proc.args.iter().map(TokenStream::from);
+        let returned = format!("{} returned: {{:?}}", proc.name);
+        let failed = format!("{} failed: {{}}", proc.name);
+        quote! {
+            #[allow(dead_code)]
+            fn #fn_name(client: &mut #client_name #(, #idents: &#types)*) {
+                match client.#proc_name(#(#idents),*) {
+                    Ok(result) => println!(#returned, result),
+                    Err(err) => eprintln!(#failed, err),
+                }
+            }
+        }
+    });
+    let calls: Vec<TokenStream> = called()
+        .filter(|proc| proc.args.is_empty())
+        .map(|proc| {
+            let fn_name = format_ident!("call_{}", proc.name.to_lowercase());
+            quote!(#fn_name(&mut client);)
+        })
+        .collect();
+    let client = if calls.is_empty() {
+        quote!(_client)
+    } else {
+        quote!(mut client)
+    };
+    quote! {
+        #[allow(non_snake_case, non_camel_case_types)]
+        mod #module;
+        use #module::*;
+
+        fn main() {
+            let host = std::env::args().nth(1).expect("Usage: <program> <host>");
+            let #client = #client_name::new(&host).expect("Server not available");
+            #(#calls)*
+        }
+
+        #(#call_fns)*
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::RPCLParser;
+    use crate::parser::Rule;
+    use pest::Parser;
+
+    fn program() -> Program {
+        let mut parsed = RPCLParser::parse(
+            Rule::program_def,
+            "program PROG {
+                version VERS {
+                    void PING(void) = 1;
+                    int ADD(int, int) = 2;
+                    int COUNT(void) = 3;
+                } = 1;
+            } = 10;",
+        )
+        .unwrap();
+        Program::from(parsed.next().unwrap())
+    }
+
+    #[test]
+    fn test_server() {
+        let generated_code = generate_server(&program(), "ProgServer");
+        let rust_code = quote! {
+            /// Server-side functions of the procedures.
+            trait ProgServer {
+                fn PING(&mut self);
+                fn ADD(&mut self, x0: i32, x1: i32) -> i32;
+                fn COUNT(&mut self) -> i32;
+
+                /// Decodes the arguments of `procedure` from `args`, calls it and encodes the result
+                /// into `reply`.
+                ///
+                /// Returns `false` if the program doesn't have a procedure with this number. Procedure
+                /// 0 (NULLPROC) is answered with an empty reply, unless the program defines it.
+                #[allow(unused_mut, unused_variables)]
+                fn dispatch(
+                    &mut self,
+                    procedure: u32,
+                    mut args: impl std::io::Read,
+                    mut reply: impl std::io::Write,
+                ) -> std::io::Result<bool> {
+                    if procedure == 1i64 as u32 {
+                        self.PING();
+                        return Ok(true);
+                    }
+                    if procedure == 2i64 as u32 {
+                        let x0 = <i32 as ::rpc_lib::XdrDeserialize>::deserialize(&mut args)?;
+                        let x1 = <i32 as ::rpc_lib::XdrDeserialize>::deserialize(&mut args)?;
+                        let result = self.ADD(x0, x1);
+                        ::rpc_lib::XdrSerialize::serialize(&result, &mut reply)?;
+                        return Ok(true);
+                    }
+                    if procedure == 3i64 as u32 {
+                        let result = self.COUNT();
+                        ::rpc_lib::XdrSerialize::serialize(&result, &mut reply)?;
+                        return Ok(true);
+                    }
+                    Ok(procedure == 0)
+                }
+            }
+        };
+        assert_eq!(
+            generated_code.to_string(),
+            rust_code.to_string(),
+            "Generated code wrong:\n{}\n{}",
+            generated_code,
+            rust_code
+        );
+    }
+
+    #[test]
+    fn test_stubs() {
+        let generated_code = generate_stubs(&program(), "ProgServer", "ProgServerImpl");
+        let rust_code = quote! {
+            struct ProgServerImpl;
+
+            impl ProgServer for ProgServerImpl {
+                fn PING(&mut self) {
+                    todo!("insert server code for PING")
+                }
+                fn ADD(&mut self, _x0: i32, _x1: i32) -> i32 {
+                    todo!("insert server code for ADD")
+                }
+                fn COUNT(&mut self) -> i32 {
+                    todo!("insert server code for COUNT")
+                }
+            }
+        };
+        assert_eq!(
+            generated_code.to_string(),
+            rust_code.to_string(),
+            "Generated code wrong:\n{}\n{}",
+            generated_code,
+            rust_code
+        );
+    }
+
+    #[test]
+    fn test_sample_main() {
+        let generated_code = generate_sample_main(&program(), "prog", "ProgClient");
+        let rust_code = quote! {
+            #[allow(non_snake_case, non_camel_case_types)]
+            mod prog;
+            use prog::*;
+
+            fn main() {
+                let host = std::env::args().nth(1).expect("Usage: <program> <host>");
+                let mut client = ProgClient::new(&host).expect("Server not available");
+                call_count(&mut client);
+            }
+
+            #[allow(dead_code)]
+            fn call_add(client: &mut ProgClient, x0: &i32, x1: &i32) {
+                match client.ADD(x0, x1) {
+                    Ok(result) => println!("ADD returned: {:?}", result),
+                    Err(err) => eprintln!("ADD failed: {}", err),
+                }
+            }
+            #[allow(dead_code)]
+            fn call_count(client: &mut ProgClient) {
+                match client.COUNT() {
+                    Ok(result) => println!("COUNT returned: {:?}", result),
+                    Err(err) => eprintln!("COUNT failed: {}", err),
+                }
+            }
+        };
+        assert_eq!(
+            generated_code.to_string(),
+            rust_code.to_string(),
+            "Generated code wrong:\n{}\n{}",
+            generated_code,
+            rust_code
+        );
+    }
+}
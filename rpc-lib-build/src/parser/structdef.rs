@@ -14,6 +14,7 @@ use quote::{format_ident, quote};
 
 use super::datatype::DataType;
 use super::declaration::{Declaration, DeclarationType};
+use super::error::Span;
 
 #[derive(PartialEq, Debug, Clone)]
 pub struct Structdef {
@@ -21,6 +22,7 @@ pub struct Structdef {
     pub struct_body: Struct,
     pub contains_vararray: bool,
     pub requires_lifetime: bool,
+    pub span: Span,
 }
 impl Structdef {
     pub fn update_contains_vararray(&mut self, typedefs_with_vararray: &HashSet<String>) {
@@ -53,9 +55,118 @@ impl Structdef {
                 _ => {}
             }
         }
+        sliced.name.push_str("_sliced");
         sliced.requires_lifetime = true;
         sliced
     }
+
+    /// Returns true if the struct is an element of a linked list, which means that its last field
+    /// is optional data of the struct itself (`struct entry { ...; entry *next; };`).
+    pub fn is_linked_list(&self) -> bool {
+        match self.struct_body.fields.last() {
+            Some(Declaration {
+                decl_type: DeclarationType::Optional,
+                data_type: DataType::TypeDef { name },
+                ..
+            }) => *name == self.name,
+            _ => false,
+        }
+    }
+}
+
+/// Serializes, deserializes and drops a linked list iteratively, so that long lists don't overflow
+/// the stack. The encoding is the same as with recursive optional data. Every node counts against
+/// the allocation budget of the current `DecodeContext`, like the elements of an `XdrList`.
+///
+/// Because of the `Drop` implementation, fields can't be moved out of a node (E0509), they have to
+/// be taken with `std::mem::take` or cloned instead.
+fn linked_list_code(struct_def: &Structdef) -> TokenStream {
+    let name = format_ident!("{}", struct_def.name);
+    let (next, fields) = struct_def.struct_body.fields.split_last().unwrap();
+    let next = format_ident!("{}", next.name);
+    let serialize = fields.iter().map(|field| {
+        let name = format_ident!("{}", field.name);
+        match field.max_len_tokens() {
+            Some(max_len) => {
+                quote!(::rpc_lib::serialize_max_len(&node.#name, &mut writer, #max_len)?;)
+            }
+            None => quote!(XdrSerialize::serialize(&node.#name, &mut writer)?;),
+        }
+    });
+    let deserialize = fields.iter().map(|field| {
+        let name = format_ident!("{}", field.name);
+        match field.max_len_tokens() {
+            Some(max_len) => {
+                quote!(#name: ::rpc_lib::deserialize_max_len(&mut reader, #max_len)?,)
+            }
+            None => quote!(#name: XdrDeserialize::deserialize(&mut reader)?,),
+        }
+    });
+    let fields: Vec<_> = fields
+        .iter()
+        .map(|field| format_ident!("{}", field.name))
+        .collect();
+
+    quote! {
+        impl XdrSerialize for #name {
+            fn len(&self) -> usize {
+                let mut len = 0;
+                let mut node = self;
+                loop {
+                    len += #(XdrSerialize::len(&node.#fields) +)* XdrSerialize::len(&0u32);
+                    match &node.#next {
+                        Some(next) => node = next,
+                        None => return len,
+                    }
+                }
+            }
+
+            fn serialize(&self, mut writer: impl std::io::Write) -> std::io::Result<()> {
+                let mut node = self;
+                loop {
+                    #(#serialize)*
+                    match &node.#next {
+                        Some(next) => {
+                            XdrSerialize::serialize(&1u32, &mut writer)?;
+                            node = next;
+                        }
+                        None => return XdrSerialize::serialize(&0u32, &mut writer),
+                    }
+                }
+            }
+        }
+
+        impl XdrDeserialize for #name {
+            fn deserialize(mut reader: impl std::io::Read) -> std::io::Result<Self> {
+                let mut nodes = std::vec::Vec::new();
+                let mut node = loop {
+                    let node = #name {
+                        #(#deserialize)*
+                        #next: None,
+                    };
+                    if !::rpc_lib::deserialize_option_discriminant(&mut reader)? {
+                        break node;
+                    }
+                    ::rpc_lib::check_allocation::<Self>(1)?;
+                    nodes.push(node);
+                };
+                while let Some(mut prev) = nodes.pop() {
+                    prev.#next = Some(std::boxed::Box::new(node));
+                    node = prev;
+                }
+                Ok(node)
+            }
+        }
+
+        impl Drop for #name {
+            fn drop(&mut self) {
+                let mut next = self.#next.take();
+                while let Some(mut node) = next {
+                    next = node.#next.take();
+                }
+            }
+        }
+    }
 }
 
 #[derive(PartialEq, Debug, Clone)]
@@ -72,15 +183,42 @@ impl From<&Structdef> for TokenStream {
         // Struct Body
         let mut struct_code = quote!();
         for field in &struct_body.fields {
-            let field_name = format_ident!("{}", &field.name);
-            let field_type = TokenStream::from(&field.data_type);
-            struct_code = quote!( #struct_code #field_name: #field_type, );
+            let field_code = TokenStream::from(field);
+            struct_code = quote!( #struct_code #field_code, );
         }
-        quote! {
-            #[derive(Debug)]
-            #[derive(::rpc_lib::XdrDeserialize, ::rpc_lib::XdrSerialize)]
-            struct #name {
-                #struct_code
+
+        if struct_def.requires_lifetime {
+            quote! {
+                #[derive(Debug)]
+                struct #name<'a> {
+                    #struct_code
+                }
+            }
+        } else if struct_def.is_linked_list() {
+            let serde_code = linked_list_code(struct_def);
+            quote! {
+                #[derive(Debug)]
+                struct #name {
+                    #struct_code
+                }
+
+                #serde_code
+            }
+        } else {
+            // Maximum lengths are checked by the derived implementations
+            let fields = struct_body.fields.iter().map(|field| {
+                let field_code = TokenStream::from(field);
+                match field.max_len_tokens() {
+                    Some(max_len) => quote!(#[xdr(max_len = #max_len)] #field_code),
+                    None => field_code,
+                }
+            });
+            quote! {
+                #[derive(Debug)]
+                #[derive(::rpc_lib::XdrDeserialize, ::rpc_lib::XdrSerialize)]
+                struct #name {
+                    #(#fields,)*
+                }
             }
         }
     }
@@ -103,6 +241,7 @@ pub fn parse_struct_type_spec(struct_type_spec: pest::iterators::Pair<'_, Rule>)
 
 impl From<pest::iterators::Pair<'_, Rule>> for Structdef {
     fn from(struct_def: pest::iterators::Pair<'_, Rule>) -> Structdef {
+        let span = Span::from(struct_def.as_span());
         let mut iter = struct_def.into_inner();
         let name = iter.next().unwrap();
         let struct_body = iter.next().unwrap();
@@ -112,6 +251,7 @@ impl From<pest::iterators::Pair<'_, Rule>> for Structdef {
             struct_body: Struct::from(struct_body),
             contains_vararray: false,
             requires_lifetime: false,
+            span,
         }
     }
 }
@@ -158,12 +298,14 @@ mod tests {
                     },
                     name: "x".into(),
                     needs_lifetime: false,
+                    max_len: None,
                 },
                 Declaration {
                     decl_type: DeclarationType::TypeNameDecl,
                     data_type: DataType::Float { length: 64 },
                     name: "f".into(),
                     needs_lifetime: false,
+                    max_len: None,
                 },
             ],
         };
@@ -177,8 +319,8 @@ mod tests {
         assert!(
             generated_code.to_string() == rust_code.to_string(),
             "Struct: Generated code wrong:\n{}\n{}",
-            generated_code.to_string(),
-            rust_code.to_string()
+            generated_code,
+            rust_code
         );
     }
 
@@ -202,6 +344,7 @@ mod tests {
                     },
                     name: "x1_".into(),
                     needs_lifetime: false,
+                    max_len: None,
                 },
                 Declaration {
                     decl_type: DeclarationType::TypeNameDecl,
@@ -210,6 +353,7 @@ mod tests {
                     },
                     name: "f".into(),
                     needs_lifetime: false,
+                    max_len: None,
                 },
             ],
         };
@@ -223,8 +367,8 @@ mod tests {
         assert!(
             generated_code.to_string() == rust_code.to_string(),
             "Struct: Generated code wrong:\n{}\n{}",
-            generated_code.to_string(),
-            rust_code.to_string()
+            generated_code,
+            rust_code
         );
     }
 
@@ -236,7 +380,8 @@ mod tests {
             "struct MyStruct_ { int x; double f; MyType t; };",
         )
         .unwrap();
-        let struct_def = Structdef::from(parsed.next().unwrap());
+        let mut struct_def = Structdef::from(parsed.next().unwrap());
+        struct_def.span = Span::default();
 
         let st = Structdef {
             name: "MyStruct_".to_string(),
@@ -251,12 +396,14 @@ mod tests {
                         },
                         name: "x".into(),
                         needs_lifetime: false,
+                        max_len: None,
                     },
                     Declaration {
                         decl_type: DeclarationType::TypeNameDecl,
                         data_type: DataType::Float { length: 64 },
                         name: "f".into(),
                         needs_lifetime: false,
+                        max_len: None,
                     },
                     Declaration {
                         decl_type: DeclarationType::TypeNameDecl,
@@ -265,10 +412,12 @@ mod tests {
                         },
                         name: "t".into(),
                         needs_lifetime: false,
+                        max_len: None,
                     },
                 ],
             },
             contains_vararray: false,
+            span: Span::default(),
         };
         assert!(struct_def == st, "Struct Def wrong");
 
@@ -281,14 +430,63 @@ mod tests {
                 f: f64,
                 t: MyType,
             }
-        }
-        .into();
+        };
         let generated_code: TokenStream = (&struct_def).into();
         assert!(
             generated_code.to_string() == rust_code.to_string(),
             "Struct: Generated code wrong:\n{}\n{}",
-            generated_code.to_string(),
-            rust_code.to_string()
+            generated_code,
+            rust_code
+        );
+    }
+
+    #[test]
+    fn struct_def_declarations() {
+        let mut parsed = RPCLParser::parse(
+            Rule::struct_def,
+            "struct Arrays { int vals<10>; opaque data[16]; string name<>; Ints ints; };",
+        )
+        .unwrap();
+        let struct_def = Structdef::from(parsed.next().unwrap());
+
+        // Code-gen
+        let rust_code: TokenStream = quote! {
+            #[derive(Debug)]
+            #[derive(::rpc_lib::XdrDeserialize, ::rpc_lib::XdrSerialize)]
+            struct Arrays {
+                #[xdr(max_len = 10usize)]
+                vals: std::vec::Vec<i32>,
+                data: [opaque; 16usize],
+                name: String,
+                ints: Ints,
+            }
+        };
+        let generated_code: TokenStream = (&struct_def).into();
+        assert!(
+            generated_code.to_string() == rust_code.to_string(),
+            "Struct: Generated code wrong:\n{}\n{}",
+            generated_code,
+            rust_code
+        );
+
+        // Sliced copy for zero-copy operations
+        let typedefs = HashSet::from(["Ints".to_string()]);
+        let sliced = struct_def.sliced_copy(&typedefs);
+        let rust_code: TokenStream = quote! {
+            #[derive(Debug)]
+            struct Arrays_sliced<'a> {
+                vals_sliced: &'a mut [i32],
+                data: [opaque; 16usize],
+                name: String,
+                ints: Ints_sliced<'a>,
+            }
+        };
+        let generated_code: TokenStream = (&sliced).into();
+        assert!(
+            generated_code.to_string() == rust_code.to_string(),
+            "Struct: Generated code wrong:\n{}\n{}",
+            generated_code,
+            rust_code
         );
     }
 
@@ -307,6 +505,7 @@ mod tests {
                 },
                 name: "x".into(),
                 needs_lifetime: false,
+                max_len: None,
             }],
         };
         assert!(struct_body == st, "Struct Type Spec wrong");
@@ -319,8 +518,46 @@ mod tests {
         assert!(
             generated_code.to_string() == rust_code.to_string(),
             "Struct: Generated code wrong:\n{}\n{}",
-            generated_code.to_string(),
-            rust_code.to_string()
+            generated_code,
+            rust_code
         );
     }
+
+    #[test]
+    fn struct_def_linked_list() {
+        let mut parsed =
+            RPCLParser::parse(Rule::struct_def, "struct entry { int id; entry *next; };").unwrap();
+        let struct_def = Structdef::from(parsed.next().unwrap());
+        assert!(struct_def.is_linked_list());
+
+        // Code-gen: no derives, but iterative implementations
+        let generated_code = TokenStream::from(&struct_def).to_string();
+        let rust_code = quote! {
+            #[derive(Debug)]
+            struct entry {
+                id: i32,
+                next: std::option::Option<std::boxed::Box<entry>>,
+            }
+        };
+        assert!(
+            generated_code.starts_with(&rust_code.to_string()),
+            "Struct: Generated code wrong:\n{}\n{}",
+            generated_code,
+            rust_code
+        );
+        for implementation in ["XdrSerialize", "XdrDeserialize", "Drop"] {
+            let impl_code = quote::format_ident!("{}", implementation);
+            let impl_code = quote!(impl #impl_code for entry).to_string();
+            assert!(
+                generated_code.contains(&impl_code),
+                "{} missing",
+                implementation
+            );
+        }
+
+        // `next` has to be the last field
+        let mut parsed =
+            RPCLParser::parse(Rule::struct_def, "struct entry { entry *next; int id; };").unwrap();
+        assert!(!Structdef::from(parsed.next().unwrap()).is_linked_list());
+    }
 }
@@ -11,8 +11,10 @@ use crate::parser::Rule;
 use proc_macro2::TokenStream;
 use quote::quote;
 
+use super::constant::Value;
 use super::datatype::DataType;
 use super::declaration::{Declaration, DeclarationType};
+use super::error::Span;
 
 #[derive(PartialEq, Debug, Clone)]
 pub struct Typedef {
@@ -20,6 +22,9 @@ pub struct Typedef {
     pub orig_type: DataType,
     pub decl_type: DeclarationType,
     pub needs_lifetime: bool,
+    /// Maximum length of variable-length data, see [`Declaration::max_len`]
+    pub max_len: Option<Value>,
+    pub span: Span,
 }
 
 impl From<&Typedef> for TokenStream {
@@ -30,6 +35,7 @@ impl From<&Typedef> for TokenStream {
             data_type: type_def.orig_type.clone(),
             decl_type: type_def.decl_type.clone(),
             needs_lifetime: type_def.needs_lifetime,
+            max_len: None,
         };
         let type_code = tmp_decl.to_rust_tokens();
         let lt = if type_def.needs_lifetime {
@@ -44,6 +50,7 @@ impl From<&Typedef> for TokenStream {
 
 impl From<pest::iterators::Pair<'_, Rule>> for Typedef {
     fn from(type_def: pest::iterators::Pair<'_, Rule>) -> Typedef {
+        let span = Span::from(type_def.as_span());
         let decl_token = type_def.into_inner().next().unwrap();
         let decl = Declaration::from(decl_token);
         let contains_vararray = decl.decl_type == DeclarationType::ArraySlice;
@@ -52,6 +59,8 @@ impl From<pest::iterators::Pair<'_, Rule>> for Typedef {
             decl_type: decl.decl_type,
             name: decl.name,
             needs_lifetime: contains_vararray,
+            max_len: decl.max_len,
+            span,
         }
     }
 }
@@ -67,7 +76,8 @@ mod tests {
         // Parser
         let mut parsed =
             RPCLParser::parse(Rule::type_def, "typedef unsigned int uint_32_t;").unwrap();
-        let typedef_generated = Typedef::from(parsed.next().unwrap());
+        let mut typedef_generated = Typedef::from(parsed.next().unwrap());
+        typedef_generated.span = Span::default();
         let typedef_coded = Typedef {
             name: "uint_32_t".to_string(),
             orig_type: DataType::Integer {
@@ -76,6 +86,8 @@ mod tests {
             },
             decl_type: DeclarationType::TypeNameDecl,
             needs_lifetime: false,
+            max_len: None,
+            span: Span::default(),
         };
         assert!(typedef_generated == typedef_coded, "Typedef parsing wrong");
 
@@ -87,8 +99,8 @@ mod tests {
         assert!(
             generated_code.to_string() == rust_code.to_string(),
             "Typedef: Generated code wrong:\n{}\n{}",
-            generated_code.to_string(),
-            rust_code.to_string()
+            generated_code,
+            rust_code
         );
     }
 
@@ -96,27 +108,31 @@ mod tests {
     fn parse_typedef_2() {
         // Parser
         let mut parsed = RPCLParser::parse(Rule::type_def, "typedef char rpc_uuid<16>;").unwrap();
-        let typedef_generated = Typedef::from(parsed.next().unwrap());
+        let mut typedef_generated = Typedef::from(parsed.next().unwrap());
+        typedef_generated.span = Span::default();
         let typedef_coded = Typedef {
             name: "rpc_uuid".to_string(),
-            orig_type: DataType::TypeDef {
-                name: "char".to_string(),
+            orig_type: DataType::Integer {
+                length: 8,
+                signed: true,
             },
             decl_type: DeclarationType::VarlenArray,
             needs_lifetime: false,
+            max_len: Some(Value::Numeric { val: 16 }),
+            span: Span::default(),
         };
         assert!(typedef_generated == typedef_coded, "Typedef parsing wrong");
 
         // Code-gen
         let rust_code: TokenStream = quote! {
-            type rpc_uuid = std::vec::Vec<char>;
+            type rpc_uuid = std::vec::Vec<i8>;
         };
         let generated_code: TokenStream = (&typedef_generated).into();
         assert!(
             generated_code.to_string() == rust_code.to_string(),
             "Typedef: Generated code wrong:\n{}\n{}",
-            generated_code.to_string(),
-            rust_code.to_string()
+            generated_code,
+            rust_code
         );
     }
 
@@ -124,7 +140,8 @@ mod tests {
     fn parse_typedef_3() {
         // Parser
         let mut parsed = RPCLParser::parse(Rule::type_def, "typedef opaque mem_data<>;").unwrap();
-        let typedef_generated = Typedef::from(parsed.next().unwrap());
+        let mut typedef_generated = Typedef::from(parsed.next().unwrap());
+        typedef_generated.span = Span::default();
         let typedef_coded = Typedef {
             name: "mem_data".to_string(),
             orig_type: DataType::TypeDef {
@@ -132,6 +149,8 @@ mod tests {
             },
             decl_type: DeclarationType::VarlenArray,
             needs_lifetime: false,
+            max_len: None,
+            span: Span::default(),
         };
         assert!(typedef_generated == typedef_coded, "Typedef parsing wrong");
 
@@ -143,8 +162,8 @@ mod tests {
         assert!(
             generated_code.to_string() == rust_code.to_string(),
             "Typedef: Generated code wrong:\n{}\n{}",
-            generated_code.to_string(),
-            rust_code.to_string()
+            generated_code,
+            rust_code
         );
     }
 }
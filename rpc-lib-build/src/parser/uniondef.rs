@@ -0,0 +1,1086 @@
+// Copyright 2022 Philipp Fensch
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::parser::Rule;
+use std::collections::HashSet;
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+
+use super::constant::Value;
+use super::datatype::DataType;
+use super::declaration::{Declaration, DeclarationType};
+use super::error::Span;
+
+#[derive(PartialEq, Clone, Debug)]
+pub(crate) enum DiscriminantType {
+    Int,
+    UnsignedInt,
+    Boolean,
+    Enum {
+        name: String,
+    },
+    /// Any other declaration, which is reported by the checks.
+    Unsupported {
+        decl: String,
+    },
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Uniondef {
+    pub name: String,
+    pub union_body: Union,
+    pub contains_vararray: bool,
+    pub requires_lifetime: bool,
+    pub span: Span,
+}
+impl Uniondef {
+    /// Checks if the Union contains [`DeclarationType::VarlenArrays`], so that a copy utilizing
+    /// slices for zero-copy operations is meaninful.
+    pub fn update_contains_vararray(&mut self, typedefs_with_vararray: &HashSet<String>) {
+        self.contains_vararray = false;
+        for (_v, d) in self.union_body.cases.iter() {
+            if d.update_contains_vararray(typedefs_with_vararray) {
+                self.contains_vararray = true
+            }
+        }
+    }
+
+    /// creates a second struct suffixed with `_sliced` that uses slices instead of arrays for
+    /// zero-copy operations.
+    pub fn sliced_copy(&self, typedefs_with_lifetime: &HashSet<String>) -> Self {
+        let mut sliced = (*self).clone();
+        for (_v, d) in sliced.union_body.cases.iter_mut() {
+            match d.decl_type {
+                DeclarationType::VarlenArray => {
+                    d.decl_type = DeclarationType::ArraySlice;
+                    d.name.push_str("_sliced");
+                }
+                DeclarationType::TypeNameDecl => {
+                    if let DataType::TypeDef { name } = &mut d.data_type {
+                        if typedefs_with_lifetime.contains(name) {
+                            name.push_str("_sliced");
+                            d.needs_lifetime = true;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        sliced.name.push_str("_sliced");
+        sliced.requires_lifetime = true;
+        sliced
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Union {
+    pub(crate) discriminant: DiscriminantType,
+    /// Case labels of each arm and its declaration
+    pub cases: std::vec::Vec<(std::vec::Vec<Value>, Declaration)>,
+    /// Spans of all case labels in the order of `cases`, used for diagnostics
+    pub(crate) label_spans: std::vec::Vec<Span>,
+    /// Arm for all other discriminants, `None` if the union has no `default:`
+    pub default: Option<std::boxed::Box<Declaration>>,
+}
+
+impl DiscriminantType {
+    /// Rust type of the discriminant.
+    fn rust_type(&self) -> TokenStream {
+        match self {
+            DiscriminantType::Int => quote!(i32),
+            DiscriminantType::UnsignedInt => quote!(u32),
+            DiscriminantType::Boolean => quote!(bool),
+            DiscriminantType::Enum { name } => {
+                let name = format_ident!("{}", name);
+                quote!(#name)
+            }
+            DiscriminantType::Unsupported { .. } => {
+                unreachable!("unsupported discriminants are rejected by the checks")
+            }
+        }
+    }
+
+    /// Returns the boolean value of a case label if the discriminant is a `bool`. `TRUE` and
+    /// `FALSE` are accepted besides numbers.
+    fn bool_value(&self, value: &Value) -> Option<bool> {
+        match (self, value) {
+            (DiscriminantType::Boolean, Value::Numeric { val }) => Some(*val != 0),
+            (DiscriminantType::Boolean, Value::Named { name }) if name == "TRUE" => Some(true),
+            (DiscriminantType::Boolean, Value::Named { name }) if name == "FALSE" => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Value of the discriminant for the case label `value`.
+    fn case_value(&self, value: &Value) -> TokenStream {
+        if let Some(val) = self.bool_value(value) {
+            return quote!(#val);
+        }
+        match (self, value) {
+            (DiscriminantType::Int, Value::Numeric { val }) => {
+                let val = *val as i32;
+                quote!(#val)
+            }
+            (DiscriminantType::UnsignedInt, Value::Numeric { val }) => {
+                let val = *val as u32;
+                quote!(#val)
+            }
+            (DiscriminantType::Enum { name: enum_name }, Value::Named { name }) => {
+                let enum_name = format_ident!("{}", enum_name);
+                let name = format_ident!("{}", name);
+                quote!(#enum_name::#name)
+            }
+            (DiscriminantType::Enum { name: _ }, Value::Numeric { val: _ }) => {
+                panic!("Union: Case has to be a case of the enum when discriminanttype is an enum!")
+            }
+            (_, Value::Named { name }) => {
+                let name = format_ident!("{}", name);
+                let rust_type = self.rust_type();
+                if *self == DiscriminantType::Boolean {
+                    quote!((#name != 0))
+                } else {
+                    quote!((#name as #rust_type))
+                }
+            }
+            (DiscriminantType::Boolean, Value::Numeric { val: _ }) => unreachable!(),
+            (DiscriminantType::Unsupported { .. }, Value::Numeric { val: _ }) => {
+                unreachable!("unsupported discriminants are rejected by the checks")
+            }
+        }
+    }
+
+    /// Condition that `discriminant` matches the case label `value`.
+    fn case_condition(&self, value: &Value, discriminant: TokenStream) -> TokenStream {
+        match self.bool_value(value) {
+            Some(true) => quote!(#discriminant),
+            Some(false) => quote!(!#discriminant),
+            None => {
+                let case_value = self.case_value(value);
+                quote!(#discriminant == #case_value)
+            }
+        }
+    }
+}
+
+/// Name of the variant of a case: the name of the constant, or `Case<number>` for numeric labels.
+fn case_ident(value: &Value) -> proc_macro2::Ident {
+    match value {
+        Value::Named { name } => format_ident!("{}", name),
+        Value::Numeric { val } if *val < 0 => format_ident!("CaseMinus{}", val.unsigned_abs()),
+        Value::Numeric { val } => format_ident!("Case{}", val.unsigned_abs()),
+    }
+}
+
+/// Variant of the generated enum for an arm of the union.
+///
+/// Arms with a single case label know their discriminant. Arms with multiple labels, named after
+/// the first one, and the default arm record the discriminant in the field `discriminant`, or in
+/// an unnamed field if they are void.
+struct Arm<'a> {
+    ident: proc_macro2::Ident,
+    /// Value of the discriminant, `None` if it is recorded
+    case_value: Option<TokenStream>,
+    /// Conditions that the recorded discriminant matches the case labels, and whether it has to
+    /// match one of them or, for the default arm, none of them
+    labels: Option<(Vec<TokenStream>, bool)>,
+    payload: Option<&'a Declaration>,
+}
+
+impl Arm<'_> {
+    fn payload_ident(&self) -> Option<proc_macro2::Ident> {
+        self.payload.map(|decl| format_ident!("{}", decl.name))
+    }
+
+    fn variant(&self, discriminant_type: &TokenStream) -> TokenStream {
+        let ident = &self.ident;
+        match (&self.case_value, self.payload) {
+            (Some(_), Some(decl)) => {
+                let decl_code = TokenStream::from(decl);
+                quote!(#ident { #decl_code })
+            }
+            (Some(_), None) => quote!(#ident),
+            (None, Some(decl)) => {
+                let decl_code = TokenStream::from(decl);
+                quote!(#ident { discriminant: #discriminant_type, #decl_code })
+            }
+            (None, None) => quote!(#ident(#discriminant_type)),
+        }
+    }
+
+    fn pattern(&self) -> TokenStream {
+        let ident = &self.ident;
+        match (&self.case_value, self.payload_ident()) {
+            (Some(_), Some(name)) => quote!(Self::#ident { #name }),
+            (Some(_), None) => quote!(Self::#ident),
+            (None, Some(name)) => quote!(Self::#ident { discriminant, #name }),
+            (None, None) => quote!(Self::#ident(discriminant)),
+        }
+    }
+
+    /// Constructs the variant after `discriminant` has been decoded.
+    fn deserialize(&self) -> TokenStream {
+        let ident = &self.ident;
+        let payload = self.payload.map(|decl| {
+            let name = format_ident!("{}", decl.name);
+            let decl_type = decl.to_rust_tokens();
+            match decl.max_len_tokens() {
+                Some(max_len) => quote! {
+                    #name: ::rpc_lib::deserialize_max_len::<#decl_type>(&mut reader, #max_len)?
+                },
+                None => quote!(#name: <#decl_type>::deserialize(&mut reader)?),
+            }
+        });
+        match (&self.case_value, payload) {
+            (Some(_), Some(payload)) => quote!(Self::#ident { #payload }),
+            (Some(_), None) => quote!(Self::#ident),
+            (None, Some(payload)) => quote!(Self::#ident { discriminant, #payload, }),
+            (None, None) => quote!(Self::#ident(discriminant)),
+        }
+    }
+
+    /// Reference to the discriminant in a match arm with [`Arm::pattern`].
+    fn discriminant(&self) -> TokenStream {
+        match &self.case_value {
+            Some(case_value) => quote!(&#case_value),
+            None => quote!(discriminant),
+        }
+    }
+
+    fn len(&self) -> TokenStream {
+        let pattern = self.pattern();
+        let discriminant = self.discriminant();
+        match self.payload_ident() {
+            Some(name) => quote! {
+                #pattern => {
+                    XdrSerialize::len(#discriminant) + XdrSerialize::len(#name)
+                }
+            },
+            None => quote!(#pattern => XdrSerialize::len(#discriminant),),
+        }
+    }
+
+    /// Rejects a recorded discriminant that doesn't belong to the arm, so that it can't be
+    /// decoded as a different arm.
+    fn check_labels(&self, union_name: &str) -> TokenStream {
+        let Some((conditions, expected)) = &self.labels else {
+            return quote!();
+        };
+        if conditions.is_empty() {
+            return quote!();
+        }
+        let mismatch = if *expected {
+            quote!(!matches)
+        } else {
+            quote!(matches)
+        };
+        let message = format!(
+            "discriminant {{:?}} doesn't belong to {}::{}",
+            union_name, self.ident
+        );
+        quote! {
+            let matches = #(#conditions)||*;
+            if #mismatch {
+                return Err(::std::io::Error::new(
+                    ::std::io::ErrorKind::InvalidInput,
+                    format!(#message, discriminant),
+                ));
+            }
+        }
+    }
+
+    fn serialize(&self, union_name: &str) -> TokenStream {
+        let pattern = self.pattern();
+        let discriminant = self.discriminant();
+        let check = self.check_labels(union_name);
+        let payload = self.payload.map(|decl| {
+            let name = format_ident!("{}", decl.name);
+            let decl_type = decl.to_rust_tokens();
+            match decl.max_len_tokens() {
+                Some(max_len) => {
+                    quote!(::rpc_lib::serialize_max_len(#name, &mut writer, #max_len)?;)
+                }
+                None => quote!(<#decl_type>::serialize(#name, &mut writer)?;),
+            }
+        });
+        if payload.is_none() && check.is_empty() {
+            return quote!(#pattern => XdrSerialize::serialize(#discriminant, &mut writer)?,);
+        }
+        quote! {
+            #pattern => {
+                #check
+                XdrSerialize::serialize(#discriminant, &mut writer)?;
+                #payload
+            }
+        }
+    }
+}
+
+fn payload(decl: &Declaration) -> Option<&Declaration> {
+    if decl.decl_type == DeclarationType::VoidDecl {
+        None
+    } else {
+        Some(decl)
+    }
+}
+
+/// Arms of the cases, without the default arm.
+fn case_arms(union: &Union) -> Vec<Arm<'_>> {
+    union
+        .cases
+        .iter()
+        .map(|(case_vals, decl)| {
+            let (case_value, labels) = match case_vals.as_slice() {
+                [case_val] => (Some(union.discriminant.case_value(case_val)), None),
+                _ => {
+                    let conditions = case_vals
+                        .iter()
+                        .map(|case_val| {
+                            union
+                                .discriminant
+                                .case_condition(case_val, quote!(*discriminant))
+                        })
+                        .collect();
+                    (None, Some((conditions, true)))
+                }
+            };
+            Arm {
+                ident: case_ident(&case_vals[0]),
+                case_value,
+                labels,
+                payload: payload(decl),
+            }
+        })
+        .collect()
+}
+
+fn default_arm(union: &Union) -> Option<Arm<'_>> {
+    let labels = union
+        .cases
+        .iter()
+        .flat_map(|(case_vals, _)| case_vals)
+        .map(|case_val| {
+            union
+                .discriminant
+                .case_condition(case_val, quote!(*discriminant))
+        })
+        .collect();
+    union.default.as_deref().map(|decl| Arm {
+        ident: format_ident!("CaseDefault"),
+        case_value: None,
+        labels: Some((labels, false)),
+        payload: payload(decl),
+    })
+}
+
+fn make_deserialize_function_code(union: &Union, union_name: &str) -> TokenStream {
+    let discriminant_type = union.discriminant.rust_type();
+    let mut cases = quote!();
+    for ((case_vals, _), arm) in union.cases.iter().zip(case_arms(union)) {
+        let conditions = case_vals.iter().map(|case_val| {
+            union
+                .discriminant
+                .case_condition(case_val, quote!(discriminant))
+        });
+        let value = arm.deserialize();
+        cases = quote! { #cases
+            if #(#conditions)||* {
+                return Ok(#value);
+            }
+        };
+    }
+
+    let default = match default_arm(union) {
+        Some(arm) => {
+            let value = arm.deserialize();
+            quote!(Ok(#value))
+        }
+        None => {
+            let message = format!("invalid discriminant {{:?}} of union {}", union_name);
+            quote! {
+                Err(::std::io::Error::new(
+                    ::std::io::ErrorKind::InvalidData,
+                    format!(#message, discriminant),
+                ))
+            }
+        }
+    };
+
+    quote! {
+        fn deserialize(mut reader: impl ::std::io::Read) -> ::std::io::Result<Self> {
+            let discriminant = <#discriminant_type>::deserialize(&mut reader)?;
+            #cases
+            #default
+        }
+    }
+}
+
+fn make_len_function_code(union: &Union) -> TokenStream {
+    let match_arms = case_arms(union)
+        .into_iter()
+        .chain(default_arm(union))
+        .map(|arm| arm.len());
+    quote! {
+        fn len(&self) -> usize {
+            match self {
+                #(#match_arms)*
+            }
+        }
+    }
+}
+
+fn make_serialization_function_code(union: &Union, union_name: &str) -> TokenStream {
+    let match_arms = case_arms(union)
+        .into_iter()
+        .chain(default_arm(union))
+        .map(|arm| arm.serialize(union_name));
+    quote! {
+        fn serialize(&self, mut writer: impl std::io::Write) -> std::io::Result<()> {
+            match self {
+                #(#match_arms)*
+            }
+            Ok(())
+        }
+    }
+}
+
+impl From<&Uniondef> for TokenStream {
+    fn from(union_def: &Uniondef) -> TokenStream {
+        let name = quote::format_ident!("{}", union_def.name);
+
+        let discriminant_type = union_def.union_body.discriminant.rust_type();
+        let variants = case_arms(&union_def.union_body)
+            .into_iter()
+            .chain(default_arm(&union_def.union_body))
+            .map(|arm| arm.variant(&discriminant_type));
+
+        let deserialization_func =
+            make_deserialize_function_code(&union_def.union_body, &union_def.name);
+        let serialization_func =
+            make_serialization_function_code(&union_def.union_body, &union_def.name);
+        let len_func = make_len_function_code(&union_def.union_body);
+
+        let lt = if union_def.requires_lifetime {
+            quote! { <'a>}
+        } else {
+            quote! {}
+        };
+        let serde_code = if union_def.requires_lifetime {
+            quote!()
+        } else {
+            quote!(
+                impl XdrDeserialize for #name {
+                    #deserialization_func
+                }
+
+                impl XdrSerialize for #name {
+                    #len_func
+
+                    #serialization_func
+                }
+            )
+        };
+
+        // Paste together
+        quote! {
+            #[derive(Debug)]
+            enum #name #lt {
+                #(#variants),*
+            }
+
+            #serde_code
+        }
+    }
+}
+
+pub fn parse_union_type_spec(union_type_spec: pest::iterators::Pair<'_, Rule>) -> Union {
+    Union::from(union_type_spec.into_inner().next().unwrap())
+}
+
+impl From<pest::iterators::Pair<'_, Rule>> for Uniondef {
+    fn from(union_def: pest::iterators::Pair<'_, Rule>) -> Uniondef {
+        let span = Span::from(union_def.as_span());
+        let mut iter = union_def.into_inner();
+        let name = iter.next().unwrap();
+        let union_body = iter.next().unwrap();
+        Uniondef {
+            name: name.as_str().to_string(),
+            union_body: Union::from(union_body),
+            contains_vararray: false,
+            requires_lifetime: false,
+            span,
+        }
+    }
+}
+
+fn parse_case_spec(
+    case_spec: pest::iterators::Pair<'_, Rule>,
+    label_spans: &mut Vec<Span>,
+) -> (Vec<Value>, Declaration) {
+    let mut values = Vec::new();
+    let mut decl = None;
+    for token in case_spec.into_inner() {
+        match token.as_rule() {
+            Rule::value => {
+                label_spans.push(Span::from(token.as_span()));
+                values.push(Value::from(token));
+            }
+            _ => decl = Some(Declaration::from(token)),
+        }
+    }
+    (values, decl.unwrap())
+}
+
+impl From<pest::iterators::Pair<'_, Rule>> for Union {
+    fn from(union_body: pest::iterators::Pair<'_, Rule>) -> Union {
+        let mut union_def = Union {
+            discriminant: DiscriminantType::Int,
+            cases: std::vec::Vec::new(),
+            label_spans: std::vec::Vec::new(),
+            default: None,
+        };
+        for token in union_body.into_inner() {
+            match token.as_rule() {
+                Rule::discriminant_decl => {
+                    let decl_token = token.into_inner().next().unwrap();
+                    let decl_str = decl_token.as_str().split_whitespace().collect::<Vec<_>>();
+                    let decl = Declaration::from(decl_token);
+                    // XDR discriminants are encoded as 32-bit integers
+                    union_def.discriminant = match (decl.decl_type, decl.data_type) {
+                        (
+                            DeclarationType::TypeNameDecl,
+                            DataType::Integer { length: 32, signed },
+                        ) => {
+                            if signed {
+                                DiscriminantType::Int
+                            } else {
+                                DiscriminantType::UnsignedInt
+                            }
+                        }
+                        (DeclarationType::TypeNameDecl, DataType::Boolean) => {
+                            DiscriminantType::Boolean
+                        }
+                        (DeclarationType::TypeNameDecl, DataType::TypeDef { name }) => {
+                            DiscriminantType::Enum { name }
+                        }
+                        _ => DiscriminantType::Unsupported {
+                            decl: decl_str.join(" "),
+                        },
+                    };
+                }
+                Rule::case_spec => {
+                    let case = parse_case_spec(token, &mut union_def.label_spans);
+                    union_def.cases.push(case);
+                }
+                Rule::declaration => {
+                    union_def.default = Some(std::boxed::Box::new(Declaration::from(token)));
+                }
+                _ => unreachable!("union_body only contains the rules above"),
+            }
+        }
+        union_def
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::RPCLParser;
+    use pest::Parser;
+
+    #[test]
+    fn parse_union_1() {
+        // Parser
+        let mut parsed = RPCLParser::parse(
+            Rule::union_body,
+            "switch(int x) {case X: int x; case Y2: unsigned hyper c; default: void; }",
+        )
+        .unwrap();
+        let union_generated = Union::from(parsed.next().unwrap());
+        let union_coded = Union {
+            discriminant: DiscriminantType::Int,
+            cases: vec![
+                (
+                    vec![Value::Named { name: "X".into() }],
+                    Declaration::from(
+                        RPCLParser::parse(Rule::declaration, "int x")
+                            .unwrap()
+                            .next()
+                            .unwrap(),
+                    ),
+                ),
+                (
+                    vec![Value::Named { name: "Y2".into() }],
+                    Declaration::from(
+                        RPCLParser::parse(Rule::declaration, "unsigned hyper c")
+                            .unwrap()
+                            .next()
+                            .unwrap(),
+                    ),
+                ),
+            ],
+            label_spans: vec![Span { start: 20, end: 21 }, Span { start: 35, end: 37 }],
+            default: Some(std::boxed::Box::new(Declaration {
+                decl_type: DeclarationType::VoidDecl,
+                data_type: DataType::Void,
+                name: "".into(),
+                needs_lifetime: false,
+                max_len: None,
+            })),
+        };
+        assert!(union_generated == union_coded, "Union parsing wrong");
+    }
+
+    #[test]
+    fn parse_union_2() {
+        // Parser
+        let mut parsed = RPCLParser::parse(
+            Rule::union_def,
+            "union MyUnion switch(unsigned int err) {case 1: int y; default: void; };",
+        )
+        .unwrap();
+        let mut union_generated = Uniondef::from(parsed.next().unwrap());
+        union_generated.span = Span::default();
+        let union_coded = Uniondef {
+            name: "MyUnion".to_string(),
+            contains_vararray: false,
+            requires_lifetime: false,
+            span: Span::default(),
+            union_body: Union {
+                discriminant: DiscriminantType::UnsignedInt,
+                cases: vec![(
+                    vec![Value::Numeric { val: 1 }],
+                    Declaration::from(
+                        RPCLParser::parse(Rule::declaration, "int y")
+                            .unwrap()
+                            .next()
+                            .unwrap(),
+                    ),
+                )],
+                label_spans: vec![Span { start: 45, end: 46 }],
+                default: Some(std::boxed::Box::new(Declaration {
+                    decl_type: DeclarationType::VoidDecl,
+                    data_type: DataType::Void,
+                    needs_lifetime: false,
+                    max_len: None,
+                    name: "".into(),
+                })),
+            },
+        };
+        assert!(union_generated == union_coded, "Union parsing wrong");
+
+        // Code-gen
+        let rust_code: TokenStream = quote! {
+            #[derive(Debug)]
+            enum MyUnion { Case1 { y: i32 }, CaseDefault(u32) }
+            impl XdrDeserialize for MyUnion {
+                fn deserialize(mut reader: impl ::std::io::Read) -> ::std::io::Result<Self> {
+                    let discriminant = <u32>::deserialize(&mut reader)?;
+                    if discriminant == 1u32 {
+                        return Ok(Self::Case1 { y: <i32>::deserialize(&mut reader)? });
+                    }
+                    Ok(Self::CaseDefault(discriminant))
+                }
+            }
+            impl XdrSerialize for MyUnion {
+                fn len(&self) -> usize {
+                    match self {
+                        Self::Case1 { y } => {
+                            XdrSerialize::len(&1u32) + XdrSerialize::len(y)
+                        }
+                        Self::CaseDefault(discriminant) => XdrSerialize::len(discriminant),
+                    }
+                }
+
+                fn serialize(&self, mut writer: impl std::io::Write) -> std::io::Result<()> {
+                    match self {
+                        Self::Case1 { y } => {
+                            XdrSerialize::serialize(&1u32, &mut writer)?;
+                            <i32>::serialize(y, &mut writer)?;
+                        }
+                        Self::CaseDefault(discriminant) => {
+                            let matches = *discriminant == 1u32;
+                            if matches {
+                                return Err(::std::io::Error::new(
+                                    ::std::io::ErrorKind::InvalidInput,
+                                    format!("discriminant {:?} doesn't belong to MyUnion::CaseDefault", discriminant),
+                                ));
+                            }
+                            XdrSerialize::serialize(discriminant, &mut writer)?;
+                        }
+                    }
+                    Ok(())
+                }
+            }
+        };
+        let generated_code: TokenStream = (&union_generated).into();
+        assert!(
+            generated_code.to_string() == rust_code.to_string(),
+            "Union: Generated code wrong:\n{}\n{}",
+            generated_code,
+            rust_code
+        );
+    }
+
+    #[test]
+    fn parse_union_3() {
+        let mut parsed = RPCLParser::parse(
+            Rule::union_type_spec,
+            "union switch(unsigned int err) {case 1: int y; default: void; }",
+        )
+        .unwrap();
+        let union_body = parse_union_type_spec(parsed.next().unwrap());
+
+        let un = Union {
+            discriminant: DiscriminantType::UnsignedInt,
+            cases: vec![(
+                vec![Value::Numeric { val: 1 }],
+                Declaration {
+                    decl_type: DeclarationType::TypeNameDecl,
+                    data_type: DataType::Integer {
+                        length: 32,
+                        signed: true,
+                    },
+                    needs_lifetime: false,
+                    max_len: None,
+                    name: "y".into(),
+                },
+            )],
+            label_spans: vec![Span { start: 37, end: 38 }],
+            default: Some(std::boxed::Box::new(Declaration {
+                decl_type: DeclarationType::VoidDecl,
+                data_type: DataType::Void,
+                name: "".into(),
+                needs_lifetime: false,
+                max_len: None,
+            })),
+        };
+        assert!(un == union_body, "Union Spec wrong");
+    }
+
+    #[test]
+    fn parse_union_def() {
+        // Parser
+        let mut parsed = RPCLParser::parse(Rule::union_def, "union MyUnion2 switch(int err) {case 0: int result; case 2: float result; default: void; };").unwrap();
+        let mut union_generated = Uniondef::from(parsed.next().unwrap());
+        union_generated.span = Span::default();
+        let union_coded = Uniondef {
+            name: "MyUnion2".to_string(),
+            contains_vararray: false,
+            requires_lifetime: false,
+            span: Span::default(),
+            union_body: Union {
+                discriminant: DiscriminantType::Int,
+                cases: vec![
+                    (
+                        vec![Value::Numeric { val: 0 }],
+                        Declaration::from(
+                            RPCLParser::parse(Rule::declaration, "int result")
+                                .unwrap()
+                                .next()
+                                .unwrap(),
+                        ),
+                    ),
+                    (
+                        vec![Value::Numeric { val: 2 }],
+                        Declaration::from(
+                            RPCLParser::parse(Rule::declaration, "float result")
+                                .unwrap()
+                                .next()
+                                .unwrap(),
+                        ),
+                    ),
+                ],
+                label_spans: vec![Span { start: 37, end: 38 }, Span { start: 57, end: 58 }],
+                default: Some(std::boxed::Box::new(Declaration {
+                    decl_type: DeclarationType::VoidDecl,
+                    data_type: DataType::Void,
+                    name: "".into(),
+                    needs_lifetime: false,
+                    max_len: None,
+                })),
+            },
+        };
+        assert!(union_generated == union_coded, "Union parsing wrong");
+
+        // Code-gen
+        let rust_code: TokenStream = quote! {
+            #[derive(Debug)]
+            enum MyUnion2 { Case0 { result: i32 }, Case2 { result: f32 }, CaseDefault(i32) }
+            impl XdrDeserialize for MyUnion2 {
+                fn deserialize(mut reader: impl ::std::io::Read) -> ::std::io::Result<Self> {
+                    let discriminant = <i32>::deserialize(&mut reader)?;
+                    if discriminant == 0i32 {
+                        return Ok(Self::Case0 { result: <i32>::deserialize(&mut reader)? });
+                    }
+                    if discriminant == 2i32 {
+                        return Ok(Self::Case2 { result: <f32>::deserialize(&mut reader)? });
+                    }
+                    Ok(Self::CaseDefault(discriminant))
+                }
+            }
+            impl XdrSerialize for MyUnion2 {
+                fn len(&self) -> usize {
+                    match self {
+                        Self::Case0 { result } => {
+                            XdrSerialize::len(&0i32) + XdrSerialize::len(result)
+                        }
+                        Self::Case2 { result } => {
+                            XdrSerialize::len(&2i32) + XdrSerialize::len(result)
+                        }
+                        Self::CaseDefault(discriminant) => XdrSerialize::len(discriminant),
+                    }
+                }
+
+                fn serialize(&self, mut writer: impl std::io::Write) -> std::io::Result<()> {
+                    match self {
+                        Self::Case0 { result } => {
+                            XdrSerialize::serialize(&0i32, &mut writer)?;
+                            <i32>::serialize(result, &mut writer)?;
+                        }
+                        Self::Case2 { result } => {
+                            XdrSerialize::serialize(&2i32, &mut writer)?;
+                            <f32>::serialize(result, &mut writer)?;
+                        }
+                        Self::CaseDefault(discriminant) => {
+                            let matches = *discriminant == 0i32 || *discriminant == 2i32;
+                            if matches {
+                                return Err(::std::io::Error::new(
+                                    ::std::io::ErrorKind::InvalidInput,
+                                    format!("discriminant {:?} doesn't belong to MyUnion2::CaseDefault", discriminant),
+                                ));
+                            }
+                            XdrSerialize::serialize(discriminant, &mut writer)?;
+                        }
+                    }
+                    Ok(())
+                }
+            }
+        };
+        let generated_code: TokenStream = (&union_generated).into();
+        assert!(
+            generated_code.to_string() == rust_code.to_string(),
+            "Union: Generated code wrong:\n{}\n{}",
+            generated_code,
+            rust_code
+        );
+    }
+
+    #[test]
+    fn union_enum_discriminant() {
+        let mut parsed = RPCLParser::parse(
+            Rule::union_def,
+            "union Res switch(Status s) {case OK: int value; case NOT_FOUND: void; default: void; };",
+        )
+        .unwrap();
+        let union_generated = Uniondef::from(parsed.next().unwrap());
+
+        let rust_code: TokenStream = quote! {
+            #[derive(Debug)]
+            enum Res { OK { value: i32 }, NOT_FOUND, CaseDefault(Status) }
+            impl XdrDeserialize for Res {
+                fn deserialize(mut reader: impl ::std::io::Read) -> ::std::io::Result<Self> {
+                    let discriminant = <Status>::deserialize(&mut reader)?;
+                    if discriminant == Status::OK {
+                        return Ok(Self::OK { value: <i32>::deserialize(&mut reader)? });
+                    }
+                    if discriminant == Status::NOT_FOUND {
+                        return Ok(Self::NOT_FOUND);
+                    }
+                    Ok(Self::CaseDefault(discriminant))
+                }
+            }
+            impl XdrSerialize for Res {
+                fn len(&self) -> usize {
+                    match self {
+                        Self::OK { value } => {
+                            XdrSerialize::len(&Status::OK) + XdrSerialize::len(value)
+                        }
+                        Self::NOT_FOUND => XdrSerialize::len(&Status::NOT_FOUND),
+                        Self::CaseDefault(discriminant) => XdrSerialize::len(discriminant),
+                    }
+                }
+
+                fn serialize(&self, mut writer: impl std::io::Write) -> std::io::Result<()> {
+                    match self {
+                        Self::OK { value } => {
+                            XdrSerialize::serialize(&Status::OK, &mut writer)?;
+                            <i32>::serialize(value, &mut writer)?;
+                        }
+                        Self::NOT_FOUND => XdrSerialize::serialize(&Status::NOT_FOUND, &mut writer)?,
+                        Self::CaseDefault(discriminant) => {
+                            let matches = *discriminant == Status::OK || *discriminant == Status::NOT_FOUND;
+                            if matches {
+                                return Err(::std::io::Error::new(
+                                    ::std::io::ErrorKind::InvalidInput,
+                                    format!("discriminant {:?} doesn't belong to Res::CaseDefault", discriminant),
+                                ));
+                            }
+                            XdrSerialize::serialize(discriminant, &mut writer)?;
+                        }
+                    }
+                    Ok(())
+                }
+            }
+        };
+        let generated_code: TokenStream = (&union_generated).into();
+        assert_eq!(
+            generated_code.to_string(),
+            rust_code.to_string(),
+            "Union: Generated code wrong:\n{}\n{}",
+            generated_code,
+            rust_code
+        );
+    }
+
+    #[test]
+    fn union_default_arms() {
+        let mut parsed = RPCLParser::parse(
+            Rule::union_def,
+            "union U switch(int x) {case 1: void; default: unsigned int other; };",
+        )
+        .unwrap();
+        let union_generated = Uniondef::from(parsed.next().unwrap());
+        let rust_code: TokenStream = quote! {
+            #[derive(Debug)]
+            enum U { Case1, CaseDefault { discriminant: i32, other: u32 } }
+            impl XdrDeserialize for U {
+                fn deserialize(mut reader: impl ::std::io::Read) -> ::std::io::Result<Self> {
+                    let discriminant = <i32>::deserialize(&mut reader)?;
+                    if discriminant == 1i32 {
+                        return Ok(Self::Case1);
+                    }
+                    Ok(Self::CaseDefault {
+                        discriminant,
+                        other: <u32>::deserialize(&mut reader)?,
+                    })
+                }
+            }
+            impl XdrSerialize for U {
+                fn len(&self) -> usize {
+                    match self {
+                        Self::Case1 => XdrSerialize::len(&1i32),
+                        Self::CaseDefault { discriminant, other } => {
+                            XdrSerialize::len(discriminant) + XdrSerialize::len(other)
+                        }
+                    }
+                }
+
+                fn serialize(&self, mut writer: impl std::io::Write) -> std::io::Result<()> {
+                    match self {
+                        Self::Case1 => XdrSerialize::serialize(&1i32, &mut writer)?,
+                        Self::CaseDefault { discriminant, other } => {
+                            let matches = *discriminant == 1i32;
+                            if matches {
+                                return Err(::std::io::Error::new(
+                                    ::std::io::ErrorKind::InvalidInput,
+                                    format!("discriminant {:?} doesn't belong to U::CaseDefault", discriminant),
+                                ));
+                            }
+                            XdrSerialize::serialize(discriminant, &mut writer)?;
+                            <u32>::serialize(other, &mut writer)?;
+                        }
+                    }
+                    Ok(())
+                }
+            }
+        };
+        let generated_code: TokenStream = (&union_generated).into();
+        assert_eq!(generated_code.to_string(), rust_code.to_string());
+
+        let mut parsed =
+            RPCLParser::parse(Rule::union_def, "union V switch(int x) {case 1: void; };").unwrap();
+        let union_generated = Uniondef::from(parsed.next().unwrap());
+        assert_eq!(union_generated.union_body.default, None);
+        let rust_code: TokenStream = quote! {
+            fn deserialize(mut reader: impl ::std::io::Read) -> ::std::io::Result<Self> {
+                let discriminant = <i32>::deserialize(&mut reader)?;
+                if discriminant == 1i32 {
+                    return Ok(Self::Case1);
+                }
+                Err(::std::io::Error::new(
+                    ::std::io::ErrorKind::InvalidData,
+                    format!("invalid discriminant {:?} of union V", discriminant),
+                ))
+            }
+        };
+        let generated_code = make_deserialize_function_code(&union_generated.union_body, "V");
+        assert_eq!(generated_code.to_string(), rust_code.to_string());
+    }
+    #[test]
+    fn union_grouped_labels() {
+        let mut parsed = RPCLParser::parse(
+            Rule::union_def,
+            "union U switch(int x) {case 1: case 2: void; case -1: case 3: int y; };",
+        )
+        .unwrap();
+        let union_generated = Uniondef::from(parsed.next().unwrap());
+        assert_eq!(union_generated.union_body.cases[0].0.len(), 2);
+        let rust_code: TokenStream = quote! {
+            #[derive(Debug)]
+            enum U { Case1(i32), CaseMinus1 { discriminant: i32, y: i32 } }
+            impl XdrDeserialize for U {
+                fn deserialize(mut reader: impl ::std::io::Read) -> ::std::io::Result<Self> {
+                    let discriminant = <i32>::deserialize(&mut reader)?;
+                    if discriminant == 1i32 || discriminant == 2i32 {
+                        return Ok(Self::Case1(discriminant));
+                    }
+                    if discriminant == -1i32 || discriminant == 3i32 {
+                        return Ok(Self::CaseMinus1 {
+                            discriminant,
+                            y: <i32>::deserialize(&mut reader)?,
+                        });
+                    }
+                    Err(::std::io::Error::new(
+                        ::std::io::ErrorKind::InvalidData,
+                        format!("invalid discriminant {:?} of union U", discriminant),
+                    ))
+                }
+            }
+            impl XdrSerialize for U {
+                fn len(&self) -> usize {
+                    match self {
+                        Self::Case1(discriminant) => XdrSerialize::len(discriminant),
+                        Self::CaseMinus1 { discriminant, y } => {
+                            XdrSerialize::len(discriminant) + XdrSerialize::len(y)
+                        }
+                    }
+                }
+
+                fn serialize(&self, mut writer: impl std::io::Write) -> std::io::Result<()> {
+                    match self {
+                        Self::Case1(discriminant) => {
+                            let matches = *discriminant == 1i32 || *discriminant == 2i32;
+                            if !matches {
+                                return Err(::std::io::Error::new(
+                                    ::std::io::ErrorKind::InvalidInput,
+                                    format!("discriminant {:?} doesn't belong to U::Case1", discriminant),
+                                ));
+                            }
+                            XdrSerialize::serialize(discriminant, &mut writer)?;
+                        }
+                        Self::CaseMinus1 { discriminant, y } => {
+                            let matches = *discriminant == -1i32 || *discriminant == 3i32;
+                            if !matches {
+                                return Err(::std::io::Error::new(
+                                    ::std::io::ErrorKind::InvalidInput,
+                                    format!("discriminant {:?} doesn't belong to U::CaseMinus1", discriminant),
+                                ));
+                            }
+                            XdrSerialize::serialize(discriminant, &mut writer)?;
+                            <i32>::serialize(y, &mut writer)?;
+                        }
+                    }
+                    Ok(())
+                }
+            }
+        };
+        let generated_code: TokenStream = (&union_generated).into();
+        assert_eq!(generated_code.to_string(), rust_code.to_string());
+    }
+}
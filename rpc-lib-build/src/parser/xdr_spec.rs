@@ -0,0 +1,627 @@
+// Copyright 2022 Philipp Fensch
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::HashSet;
+
+use crate::parser::declaration::DeclarationType;
+use crate::parser::Rule;
+
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use super::constant::{ConstantDeclaration, Value};
+use super::datatype::DataType;
+use super::declaration::Declaration;
+use super::enumdef::Enumdef;
+use super::error::Span;
+use super::program::Program;
+use super::structdef::Structdef;
+use super::typedef::Typedef;
+use super::uniondef::Uniondef;
+
+pub enum ResolvedType<'a> {
+    Struct(&'a Structdef),
+    Union(&'a Uniondef),
+    Enum(&'a Enumdef),
+    // TODO: Consts as well?
+}
+
+/// Definitions of anonymous types, which are named after the declaration they appear in.
+#[derive(Default)]
+struct AnonymousTypes {
+    structs: Vec<Structdef>,
+    unions: Vec<Uniondef>,
+    enums: Vec<Enumdef>,
+}
+
+/// Fields of the structs and arms of the unions
+fn declarations_mut<'a>(
+    structs: &'a mut [Structdef],
+    unions: &'a mut [Uniondef],
+) -> impl Iterator<Item = &'a mut Declaration> {
+    structs
+        .iter_mut()
+        .flat_map(|s| s.struct_body.fields.iter_mut())
+        .chain(unions.iter_mut().flat_map(|u| {
+            let union_body = &mut u.union_body;
+            union_body
+                .cases
+                .iter_mut()
+                .map(|(_, decl)| decl)
+                .chain(union_body.default.as_deref_mut())
+        }))
+}
+
+/// Maximum length of the typedef `name`, following aliases of aliases.
+fn typedef_max_len<'a>(typedefs: &'a [Typedef], mut name: &'a str) -> Option<Value> {
+    for _ in 0..typedefs.len() {
+        let typedef = typedefs.iter().find(|td| td.name == name)?;
+        if typedef.max_len.is_some() {
+            return typedef.max_len.clone();
+        }
+        match (&typedef.decl_type, &typedef.orig_type) {
+            (DeclarationType::TypeNameDecl, DataType::TypeDef { name: orig }) => name = orig,
+            _ => return None,
+        }
+    }
+    None
+}
+
+impl AnonymousTypes {
+    /// Replaces an anonymous type by a definition called `name` and names the anonymous types
+    /// contained in it.
+    fn name(&mut self, data_type: &mut DataType, name: String, span: Span) {
+        let named = DataType::TypeDef { name: name.clone() };
+        match std::mem::replace(data_type, named) {
+            DataType::Struct { def } => {
+                let mut structdef = Structdef {
+                    name,
+                    struct_body: def,
+                    contains_vararray: false,
+                    requires_lifetime: false,
+                    span,
+                };
+                self.name_in_decls(
+                    &structdef.name,
+                    structdef.struct_body.fields.iter_mut(),
+                    span,
+                );
+                self.structs.push(structdef);
+            }
+            DataType::Union { def } => {
+                let mut uniondef = Uniondef {
+                    name,
+                    union_body: def,
+                    contains_vararray: false,
+                    requires_lifetime: false,
+                    span,
+                };
+                let union_body = &mut uniondef.union_body;
+                let decls = union_body
+                    .cases
+                    .iter_mut()
+                    .map(|(_, decl)| decl)
+                    .chain(union_body.default.as_deref_mut());
+                self.name_in_decls(&uniondef.name, decls, span);
+                self.unions.push(uniondef);
+            }
+            DataType::Enum { def } => self.enums.push(Enumdef {
+                name,
+                enum_body: def,
+                span,
+            }),
+            other => *data_type = other,
+        }
+    }
+
+    /// Names the anonymous types of the fields of `parent` `<parent>_<field>`.
+    fn name_in_decls<'a>(
+        &mut self,
+        parent: &str,
+        decls: impl Iterator<Item = &'a mut Declaration>,
+        span: Span,
+    ) {
+        for decl in decls {
+            let name = format!("{}_{}", parent, decl.name);
+            self.name(&mut decl.data_type, name, span);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Specification {
+    pub typedefs: std::vec::Vec<Typedef>,
+    pub enums: std::vec::Vec<Enumdef>,
+    pub structs: std::vec::Vec<Structdef>,
+    pub unions: std::vec::Vec<Uniondef>,
+    pub constants: std::vec::Vec<ConstantDeclaration>,
+    pub union_typedefs_with_vararray: HashSet<String>,
+}
+impl Specification {
+    /// Creates a copy of all datatypes that are of type [`DeclarationType::VarlenArray`]. These
+    /// can be used for zero-copy operation variants of functions.
+    pub fn update_contains_vararray(&mut self) {
+        // TODO: Nested typedefs are not handled
+        let mut vararray_typedefs: HashSet<String> = HashSet::new();
+        let sliced_typedefs: Vec<Typedef> = self
+            .typedefs
+            .iter()
+            .filter(|td| td.decl_type == DeclarationType::VarlenArray)
+            .map(|td| {
+                vararray_typedefs.insert(td.name.clone());
+                let mut sliced_td = (*td).clone();
+                sliced_td.decl_type = DeclarationType::ArraySlice;
+                sliced_td.name.push_str("_sliced");
+                sliced_td.needs_lifetime = true;
+                sliced_td
+            })
+            .collect();
+        self.typedefs.extend_from_slice(sliced_typedefs.as_slice());
+
+        self.structs
+            .iter_mut()
+            .for_each(|s| s.update_contains_vararray(&vararray_typedefs));
+
+        let sliced_structs: Vec<Structdef> = self
+            .structs
+            .iter()
+            .filter(|s| s.contains_vararray)
+            .map(|s| s.sliced_copy(&vararray_typedefs))
+            .collect();
+        self.structs.extend(sliced_structs);
+
+        self.unions
+            .iter_mut()
+            .for_each(|u| u.update_contains_vararray(&vararray_typedefs));
+        let sliced_unions: Vec<Uniondef> = self
+            .unions
+            .iter()
+            .filter(|u| u.contains_vararray)
+            .map(|u| {
+                self.union_typedefs_with_vararray.insert(u.name.clone());
+                u.sliced_copy(&vararray_typedefs)
+            })
+            .collect();
+        self.unions.extend(sliced_unions);
+    }
+
+    /// Maps linked lists (see [`Structdef::is_linked_list`]) to `XdrList`s of their elements.
+    /// The elements lose their `next` field, every optional data of the element type becomes a
+    /// list. Lists whose element type is also used directly, e.g. as a field or procedure
+    /// argument, are kept, since those uses would lose the `next` field on the wire.
+    pub fn map_linked_lists_to_vec(&mut self, program: &Program) {
+        let mut lists: HashSet<String> = self
+            .structs
+            .iter()
+            .filter(|structdef| structdef.is_linked_list())
+            .map(|structdef| structdef.name.clone())
+            .collect();
+        let fields = self
+            .structs
+            .iter()
+            .flat_map(|s| s.struct_body.fields.iter())
+            .chain(self.unions.iter().flat_map(|u| {
+                let union_body = &u.union_body;
+                union_body
+                    .cases
+                    .iter()
+                    .map(|(_, decl)| decl)
+                    .chain(union_body.default.as_deref())
+            }))
+            .map(|decl| (&decl.decl_type, &decl.data_type));
+        let typedefs = self
+            .typedefs
+            .iter()
+            .map(|typedef| (&typedef.decl_type, &typedef.orig_type));
+        let procedures = program
+            .versions
+            .iter()
+            .flat_map(|version| version.procedures.iter())
+            .flat_map(|procedure| {
+                procedure
+                    .args
+                    .iter()
+                    .chain(std::iter::once(&procedure.return_type))
+            })
+            .map(|data_type| (&DeclarationType::TypeNameDecl, data_type));
+        for (decl_type, data_type) in fields.chain(typedefs).chain(procedures) {
+            if let (false, DataType::TypeDef { name }) =
+                (*decl_type == DeclarationType::Optional, data_type)
+            {
+                lists.remove(name);
+            }
+        }
+        for structdef in self.structs.iter_mut() {
+            if lists.contains(&structdef.name) {
+                structdef.struct_body.fields.pop();
+            }
+        }
+
+        let is_list = |decl_type: &DeclarationType, data_type: &DataType| match data_type {
+            DataType::TypeDef { name } => {
+                *decl_type == DeclarationType::Optional && lists.contains(name)
+            }
+            _ => false,
+        };
+        let decls = declarations_mut(&mut self.structs, &mut self.unions);
+        for decl in decls {
+            if is_list(&decl.decl_type, &decl.data_type) {
+                decl.decl_type = DeclarationType::List;
+            }
+        }
+        for typedef in self.typedefs.iter_mut() {
+            if is_list(&typedef.decl_type, &typedef.orig_type) {
+                typedef.decl_type = DeclarationType::List;
+            }
+        }
+    }
+
+    /// Maps strings to `XdrString`, which holds arbitrary bytes instead of UTF-8.
+    pub fn map_strings_to_bytes(&mut self, program: &mut Program) {
+        let data_types = declarations_mut(&mut self.structs, &mut self.unions)
+            .map(|decl| &mut decl.data_type)
+            .chain(self.typedefs.iter_mut().map(|td| &mut td.orig_type))
+            .chain(
+                program
+                    .versions
+                    .iter_mut()
+                    .flat_map(|version| version.procedures.iter_mut())
+                    .flat_map(|procedure| {
+                        procedure
+                            .args
+                            .iter_mut()
+                            .chain(std::iter::once(&mut procedure.return_type))
+                    }),
+            );
+        for data_type in data_types {
+            if *data_type == DataType::String {
+                *data_type = DataType::ByteString;
+            }
+        }
+    }
+
+    /// Applies the maximum length of typedefs like `typedef int Ints<10>;` to the fields, union
+    /// arms, procedure arguments and results of that type, whose encoding checks it.
+    pub fn apply_typedef_max_lens(&mut self, program: &mut Program) {
+        let decls = declarations_mut(&mut self.structs, &mut self.unions);
+        for decl in decls {
+            if let (DeclarationType::TypeNameDecl, DataType::TypeDef { name }, None) =
+                (&decl.decl_type, &decl.data_type, &decl.max_len)
+            {
+                decl.max_len = typedef_max_len(&self.typedefs, name);
+            }
+        }
+        let max_len = |data_type: &DataType| match data_type {
+            DataType::TypeDef { name } => typedef_max_len(&self.typedefs, name),
+            _ => None,
+        };
+        let procedures = program
+            .versions
+            .iter_mut()
+            .flat_map(|version| version.procedures.iter_mut());
+        for procedure in procedures {
+            procedure.arg_max_lens = procedure.args.iter().map(max_len).collect();
+            procedure.return_max_len = max_len(&procedure.return_type);
+        }
+    }
+
+    /// Turns anonymous struct, union and enum types into definitions named after the declaration
+    /// they appear in: `<parent>_<field>` for fields and union arms, the name of the typedef for
+    /// `typedef struct {...} name;`, and `<procedure>_arg<n>` or `<procedure>_result` for
+    /// procedures.
+    pub fn name_anonymous_types(&mut self, program: &mut Program) {
+        let mut anonymous = AnonymousTypes::default();
+        for structdef in self.structs.iter_mut() {
+            let fields = structdef.struct_body.fields.iter_mut();
+            anonymous.name_in_decls(&structdef.name, fields, structdef.span);
+        }
+        for uniondef in self.unions.iter_mut() {
+            let union_body = &mut uniondef.union_body;
+            let decls = union_body
+                .cases
+                .iter_mut()
+                .map(|(_, decl)| decl)
+                .chain(union_body.default.as_deref_mut());
+            anonymous.name_in_decls(&uniondef.name, decls, uniondef.span);
+        }
+        self.typedefs.retain_mut(|typedef| {
+            let is_anonymous = matches!(
+                typedef.orig_type,
+                DataType::Struct { .. } | DataType::Union { .. } | DataType::Enum { .. }
+            );
+            if !is_anonymous {
+                return true;
+            }
+            // `typedef struct {...} name;` defines the struct `name` itself
+            if typedef.decl_type == DeclarationType::TypeNameDecl {
+                let name = typedef.name.clone();
+                anonymous.name(&mut typedef.orig_type, name, typedef.span);
+                return false;
+            }
+            let name = format!("{}_element", typedef.name);
+            anonymous.name(&mut typedef.orig_type, name, typedef.span);
+            true
+        });
+        for procedure in program
+            .versions
+            .iter_mut()
+            .flat_map(|version| version.procedures.iter_mut())
+        {
+            for (i, arg) in procedure.args.iter_mut().enumerate() {
+                let name = format!("{}_arg{}", procedure.name, i + 1);
+                anonymous.name(arg, name, procedure.span);
+            }
+            let name = format!("{}_result", procedure.name);
+            anonymous.name(&mut procedure.return_type, name, procedure.span);
+        }
+        self.structs.extend(anonymous.structs);
+        self.unions.extend(anonymous.unions);
+        self.enums.extend(anonymous.enums);
+    }
+
+    pub fn get_type_specification<'a>(&'a self, name: &str) -> Option<ResolvedType<'a>> {
+        for s in &self.structs {
+            if s.name == name {
+                return Some(ResolvedType::Struct(s));
+            }
+        }
+        for u in &self.unions {
+            if u.name == name {
+                return Some(ResolvedType::Union(u));
+            }
+        }
+        for e in &self.enums {
+            if e.name == name {
+                return Some(ResolvedType::Enum(e));
+            }
+        }
+        None
+    }
+}
+
+impl From<&Specification> for TokenStream {
+    fn from(spec: &Specification) -> TokenStream {
+        let mut code = quote!();
+        for typedef in &spec.typedefs {
+            let def: TokenStream = typedef.into();
+            code = quote!( #code #def );
+        }
+        for enumdef in &spec.enums {
+            let def: TokenStream = enumdef.into();
+            code = quote!( #code #def );
+        }
+        for structdef in &spec.structs {
+            let def: TokenStream = structdef.into();
+            code = quote!( #code #def );
+        }
+        for uniondef in &spec.unions {
+            let def: TokenStream = uniondef.into();
+            code = quote!( #code #def );
+        }
+        for constant in &spec.constants {
+            let def: TokenStream = constant.into();
+            code = quote!( #code #def );
+        }
+        quote!(type opaque = u8; #code)
+    }
+}
+
+impl From<pest::iterators::Pair<'_, Rule>> for Specification {
+    fn from(specification: pest::iterators::Pair<'_, Rule>) -> Specification {
+        let mut spec = Specification {
+            typedefs: std::vec::Vec::new(),
+            enums: std::vec::Vec::new(),
+            structs: std::vec::Vec::new(),
+            unions: std::vec::Vec::new(),
+            constants: std::vec::Vec::new(),
+            union_typedefs_with_vararray: HashSet::new(),
+        };
+        for definition in specification.into_inner() {
+            match definition.as_rule() {
+                Rule::type_def => {
+                    spec.typedefs.push(Typedef::from(definition));
+                }
+                Rule::enum_def => {
+                    spec.enums.push(Enumdef::from(definition));
+                }
+                Rule::struct_def => {
+                    spec.structs.push(Structdef::from(definition));
+                }
+                Rule::union_def => {
+                    spec.unions.push(Uniondef::from(definition));
+                }
+                Rule::constant_def => {
+                    spec.constants.push(ConstantDeclaration::from(definition));
+                }
+                _ => eprintln!("Unknown Definition"),
+            }
+        }
+        spec
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::RPCLParser;
+    use pest::Parser;
+
+    #[test]
+    fn parse_specification_1() {
+        let s = "const CON = 1;
+        struct X {
+            int x;
+            int y;
+        };
+        const CON2 = 2;
+        enum X {
+            X1 = 1,
+            X2 = 2
+        };
+        ";
+        let mut parsed = RPCLParser::parse(Rule::specification, s).unwrap();
+        let spec = Specification::from(parsed.next().unwrap());
+
+        assert!(
+            spec.constants.len() == 2,
+            "Number of parsed constants wrong"
+        );
+        assert!(spec.structs.len() == 1, "Number of parsed structs wrong");
+        assert!(spec.enums.len() == 1, "Number of parsed enums wrong");
+    }
+
+    #[test]
+    fn parse_specification_2() {
+        let s = "union MyUnion switch(int err) {
+            case 0:
+                int result;
+            default:
+                void;
+        };
+        union MyUnion2 switch(int err) {
+            case 0:
+                int result;
+            case 2:
+                float result;
+            default:
+                void;
+        };
+        typedef unsigned int u_int_32;
+        typedef Type1 Type2;
+        ";
+        let mut parsed = RPCLParser::parse(Rule::specification, s).unwrap();
+        let spec = Specification::from(parsed.next().unwrap());
+
+        assert!(spec.unions.len() == 2, "Number of parsed unions wrong");
+        assert!(spec.typedefs.len() == 2, "Number of parsed typedefs wrong");
+    }
+
+    #[test]
+    fn linked_lists_as_vec() {
+        let s = "struct entry {
+            int id;
+            entry *next;
+        };
+        struct dirlist {
+            entry *entries;
+            int *count;
+        };
+        typedef entry *entrylist;
+        program PROG {
+            version VERS {
+                entrylist LIST(dirlist) = 1;
+            } = 1;
+        } = 10;";
+        let (mut spec, program) = crate::parser::parse_file(s).unwrap();
+        assert!(spec.structs[0].is_linked_list());
+        assert!(!spec.structs[1].is_linked_list());
+
+        spec.map_linked_lists_to_vec(&program);
+        let entry = &spec.structs[0].struct_body.fields;
+        assert_eq!(entry.len(), 1, "`next` field not removed");
+        let dirlist = &spec.structs[1].struct_body.fields;
+        assert_eq!(dirlist[0].decl_type, DeclarationType::List);
+        assert_eq!(dirlist[1].decl_type, DeclarationType::Optional);
+        assert_eq!(spec.typedefs[0].decl_type, DeclarationType::List);
+    }
+
+    #[test]
+    fn linked_lists_used_directly() {
+        let s = "struct entry { int v; entry *next; };
+        struct holder { entry head; entry *rest; };
+        struct node { int v; node *next; };
+        program PROG {
+            version VERS {
+                node FIRST(void) = 1;
+            } = 1;
+        } = 10;";
+        let (mut spec, program) = crate::parser::parse_file(s).unwrap();
+        spec.map_linked_lists_to_vec(&program);
+        for structdef in &spec.structs {
+            assert!(structdef.is_linked_list() || structdef.name == "holder");
+        }
+        let holder = &spec.structs[1].struct_body.fields;
+        assert_eq!(holder[1].decl_type, DeclarationType::Optional);
+    }
+    #[test]
+    fn anonymous_types() {
+        let s = "struct outer {
+            struct { union switch (int x) { case 1: enum { A = 1 } e; } u; } inner;
+        };
+        typedef struct { int a; } alias;
+        typedef struct { int b; } list<>;
+        program PROG {
+            version VERS {
+                struct { int r; } FUNC(int, union switch (bool b) { case TRUE: void; }) = 1;
+            } = 1;
+        } = 10;";
+        let (spec, program) = crate::parser::parse_file(s).unwrap();
+        let names = |names: Vec<&str>| names.join(" ");
+        assert_eq!(
+            names(spec.structs.iter().map(|s| s.name.as_str()).collect()),
+            "outer outer_inner alias list_element FUNC_result"
+        );
+        assert_eq!(
+            names(spec.unions.iter().map(|u| u.name.as_str()).collect()),
+            "outer_inner_u FUNC_arg2"
+        );
+        assert_eq!(spec.enums[0].name, "outer_inner_u_e");
+        assert_eq!(
+            names(spec.typedefs.iter().map(|t| t.name.as_str()).collect()),
+            "list"
+        );
+        assert_eq!(
+            spec.structs[0].struct_body.fields[0].data_type,
+            DataType::TypeDef {
+                name: "outer_inner".into()
+            }
+        );
+        let procedure = &program.versions[0].procedures[0];
+        assert_eq!(
+            procedure.args[1],
+            DataType::TypeDef {
+                name: "FUNC_arg2".into()
+            }
+        );
+    }
+    #[test]
+    fn typedef_max_lens() {
+        let s = "typedef string Name<8>;
+        typedef Name Alias;
+        struct X { Name a; Alias b; Name c<4>; Name *d; };
+        union U switch (int x) { case 1: Alias e; };
+        program PROG {
+            version VERS {
+                int SET(Alias, int) = 1;
+                Name GET(void) = 2;
+            } = 1;
+        } = 10;";
+        let (mut spec, mut program) = crate::parser::parse_file(s).unwrap();
+        spec.apply_typedef_max_lens(&mut program);
+        let max_lens: Vec<_> = spec.structs[0]
+            .struct_body
+            .fields
+            .iter()
+            .map(|decl| decl.max_len.clone())
+            .collect();
+        let eight = Some(Value::Numeric { val: 8 });
+        assert_eq!(
+            max_lens,
+            [
+                eight.clone(),
+                eight.clone(),
+                Some(Value::Numeric { val: 4 }),
+                None
+            ]
+        );
+        assert_eq!(spec.unions[0].union_body.cases[0].1.max_len, eight);
+        let procedures = &program.versions[0].procedures;
+        assert_eq!(procedures[0].arg_max_lens, [eight.clone(), None]);
+        assert_eq!(procedures[1].return_max_len, eight);
+    }
+}
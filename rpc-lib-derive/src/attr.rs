@@ -0,0 +1,291 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote, ToTokens};
+use syn::parse::ParseStream;
+use syn::{Attribute, DataEnum, Expr, Field, Fields, Ident, LitStr, Path, Token, Variant};
+
+/// Field of a struct or an enum variant together with its `#[xdr(...)]` attributes.
+pub struct XdrField {
+    /// `#[xdr(skip)]`: not encoded, decoded as `Default::default()`
+    skip: bool,
+    /// `#[xdr(with = "module")]`: encoded with `module::{len, serialize, deserialize}`
+    with: Option<Path>,
+    /// `#[xdr(max_len = N)]`: variable-length data with at most `N` elements
+    max_len: Option<Expr>,
+    /// `#[xdr(opaque)]`: encoded as variable-length opaque data through `AsRef<[u8]>` and
+    /// `From<Vec<u8>>`
+    opaque: bool,
+}
+
+impl XdrField {
+    /// Returns true if the field isn't encoded.
+    pub fn skipped(&self) -> bool {
+        self.skip
+    }
+
+    /// Reference to the value, for the given place expression of the field (`self.x`) and a
+    /// reference to it (`&self.x`).
+    fn value(&self, reference: &TokenStream) -> TokenStream {
+        if self.opaque {
+            quote!(::std::convert::AsRef::<[u8]>::as_ref(#reference))
+        } else {
+            reference.clone()
+        }
+    }
+
+    /// Summand of the encoded length, empty for skipped fields.
+    pub fn len(&self, reference: TokenStream) -> TokenStream {
+        if self.skip {
+            quote!()
+        } else if let Some(with) = &self.with {
+            quote!(#with::len(#reference) +)
+        } else {
+            let value = self.value(&reference);
+            quote!(XdrSerialize::len(#value) +)
+        }
+    }
+
+    /// Statement encoding the field into `writer`.
+    pub fn serialize(&self, place: TokenStream, reference: TokenStream) -> TokenStream {
+        if self.skip {
+            quote!()
+        } else if let Some(with) = &self.with {
+            quote!(#with::serialize(#reference, &mut writer)?;)
+        } else if let Some(max_len) = &self.max_len {
+            let value = self.value(&reference);
+            quote!(::rpc_lib::serialize_max_len(#value, &mut writer, #max_len)?;)
+        } else if self.opaque {
+            let value = self.value(&reference);
+            quote!(XdrSerialize::serialize(#value, &mut writer)?;)
+        } else {
+            quote!(#place.serialize(&mut writer)?;)
+        }
+    }
+
+    /// Statements encoding the field into `buf` at `offset`, which is advanced past the field.
+    /// Fields with a codec or a maximum length are encoded through a writer over the slice.
+    pub fn encode(&self, place: TokenStream, reference: TokenStream) -> TokenStream {
+        if self.skip {
+            quote!()
+        } else if self.with.is_none() && self.max_len.is_none() {
+            let value = self.value(&reference);
+            quote!(offset += XdrSerialize::encode_into(#value, &mut buf[offset..])?;)
+        } else {
+            let serialize = self.serialize(place, reference);
+            quote! {
+                {
+                    let mut writer = &mut buf[offset..];
+                    let available = writer.len();
+                    #serialize
+                    offset += available - writer.len();
+                }
+            }
+        }
+    }
+
+    /// Expression decoding the field from `reader`, which is a mutable reference to the reader.
+    /// With `borrowed`, `reader` is a `&mut &[u8]` and the field may borrow from it.
+    pub fn deserialize(&self, reader: &TokenStream, borrowed: bool) -> TokenStream {
+        if self.skip {
+            return quote!(::std::default::Default::default());
+        }
+        if let Some(with) = &self.with {
+            return quote!(#with::deserialize(#reader)?);
+        }
+        let ty = if self.opaque {
+            quote!(::std::vec::Vec<u8>)
+        } else {
+            quote!(_)
+        };
+        let value = match &self.max_len {
+            Some(max_len) => {
+                quote!(::rpc_lib::deserialize_max_len::<#ty>(#reader, #max_len)?)
+            }
+            None if self.opaque => quote!(<#ty as XdrDeserialize>::deserialize(#reader)?),
+            None if borrowed => {
+                quote!(::rpc_lib::XdrDeserializeBorrowed::deserialize_borrowed(#reader)?)
+            }
+            None => quote!(XdrDeserialize::deserialize(#reader)?),
+        };
+        if self.opaque {
+            quote!(::std::convert::From::from(#value))
+        } else {
+            value
+        }
+    }
+}
+
+fn parse_field(field: &Field) -> syn::Result<XdrField> {
+    let mut xdr_field = XdrField {
+        skip: false,
+        with: None,
+        max_len: None,
+        opaque: false,
+    };
+    for attr in field.attrs.iter().filter(|attr| attr.path.is_ident("xdr")) {
+        attr.parse_args_with(|input: ParseStream<'_>| {
+            while !input.is_empty() {
+                let key: Ident = input.parse()?;
+                if key == "skip" {
+                    xdr_field.skip = true;
+                } else if key == "opaque" {
+                    xdr_field.opaque = true;
+                } else if key == "with" {
+                    input.parse::<Token![=]>()?;
+                    xdr_field.with = Some(input.parse::<LitStr>()?.parse()?);
+                } else if key == "max_len" {
+                    input.parse::<Token![=]>()?;
+                    xdr_field.max_len = Some(input.parse()?);
+                } else {
+                    return Err(syn::Error::new(key.span(), "unknown xdr attribute"));
+                }
+                if !input.is_empty() {
+                    input.parse::<Token![,]>()?;
+                }
+            }
+            Ok(())
+        })?;
+    }
+    if xdr_field.with.is_some() && (xdr_field.max_len.is_some() || xdr_field.opaque) {
+        return Err(syn::Error::new_spanned(
+            field,
+            "`with` can't be combined with `max_len` or `opaque`",
+        ));
+    }
+    Ok(xdr_field)
+}
+
+/// Parses the `#[xdr(...)]` attributes of the fields.
+pub fn fields(fields: &Fields) -> syn::Result<Vec<XdrField>> {
+    fields.iter().map(parse_field).collect()
+}
+
+/// Variant of an enum together with its discriminant on the wire.
+pub struct XdrVariant<'a> {
+    pub variant: &'a Variant,
+    /// Expression of type `i32`
+    pub case: TokenStream,
+    /// The variant is the `default` arm, its first field holds the discriminant
+    pub default: bool,
+}
+
+impl XdrVariant<'_> {
+    /// Identifiers to bind the fields of the variant to in a pattern, and the pattern itself.
+    pub fn bindings(&self) -> (Vec<Ident>, TokenStream) {
+        let ident = &self.variant.ident;
+        match &self.variant.fields {
+            Fields::Named(fields) => {
+                let idents: Vec<Ident> = fields
+                    .named
+                    .iter()
+                    .map(|field| field.ident.clone().unwrap())
+                    .collect();
+                let pattern = quote!(Self::#ident { #(#idents),* });
+                (idents, pattern)
+            }
+            Fields::Unnamed(fields) => {
+                let idents: Vec<Ident> = (0..fields.unnamed.len())
+                    .map(|i| format_ident!("x{}", i))
+                    .collect();
+                let pattern = quote!(Self::#ident(#(#idents),*));
+                (idents, pattern)
+            }
+            Fields::Unit => (Vec::new(), quote!(Self::#ident)),
+        }
+    }
+}
+
+#[derive(Default)]
+struct VariantAttrs {
+    case: Option<Expr>,
+    default: bool,
+}
+
+fn parse_variant_attrs(attrs: &[Attribute]) -> syn::Result<VariantAttrs> {
+    let mut variant_attrs = VariantAttrs::default();
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("xdr")) {
+        attr.parse_args_with(|input: ParseStream<'_>| {
+            while !input.is_empty() {
+                let key: Ident = input.parse()?;
+                if key == "value" || key == "case" {
+                    input.parse::<Token![=]>()?;
+                    variant_attrs.case = Some(input.parse()?);
+                } else if key == "default" {
+                    variant_attrs.default = true;
+                } else {
+                    return Err(syn::Error::new(key.span(), "unknown xdr attribute"));
+                }
+                if !input.is_empty() {
+                    input.parse::<Token![,]>()?;
+                }
+            }
+            Ok(())
+        })?;
+    }
+    Ok(variant_attrs)
+}
+
+/// Parenthesizes `expr` unless it is a literal or a path, so that it can be used as an operand.
+fn operand(expr: &Expr) -> TokenStream {
+    match expr {
+        Expr::Lit(_) | Expr::Path(_) | Expr::Paren(_) | Expr::Unary(_) => expr.to_token_stream(),
+        _ => quote!((#expr)),
+    }
+}
+
+/// Determines the discriminants of the variants of an enum.
+///
+/// The discriminant is given by `#[xdr(value = N)]` or `#[xdr(case = N)]`, by the discriminant of
+/// the Rust enum, or it is the discriminant of the previous variant plus one, starting at 0, like
+/// implicit discriminants in Rust. The variant marked with `#[xdr(default)]` matches all other
+/// discriminants.
+pub fn variants(data_enum: &DataEnum) -> syn::Result<Vec<XdrVariant<'_>>> {
+    let mut variants = Vec::new();
+    let mut base: Option<TokenStream> = None;
+    let mut offset = 0i32;
+    let mut has_default = false;
+    for variant in &data_enum.variants {
+        let attrs = parse_variant_attrs(&variant.attrs)?;
+        if attrs.default {
+            if has_default {
+                return Err(syn::Error::new_spanned(
+                    variant,
+                    "only one variant can be the default",
+                ));
+            }
+            if variant.fields.is_empty() {
+                return Err(syn::Error::new_spanned(
+                    variant,
+                    "the default variant needs a field for the discriminant",
+                ));
+            }
+            has_default = true;
+            variants.push(XdrVariant {
+                variant,
+                case: quote!(),
+                default: true,
+            });
+            continue;
+        }
+
+        let explicit = attrs
+            .case
+            .as_ref()
+            .or(variant.discriminant.as_ref().map(|(_, expr)| expr));
+        if let Some(expr) = explicit {
+            base = Some(operand(expr));
+            offset = 0;
+        }
+        let case = match &base {
+            Some(base) if offset == 0 => base.clone(),
+            Some(base) => quote!((#base + #offset)),
+            None => quote!(#offset),
+        };
+        offset += 1;
+        variants.push(XdrVariant {
+            variant,
+            case,
+            default: false,
+        });
+    }
+    Ok(variants)
+}
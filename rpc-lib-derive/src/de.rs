@@ -1,44 +1,240 @@
 use proc_macro2::TokenStream;
 use quote::quote;
-use syn::{Data, DataStruct, DeriveInput, Fields, Ident};
+use syn::{
+    parse_quote, Data, DataEnum, DataStruct, DeriveInput, Fields, GenericParam, Generics, Ident,
+};
+
+use crate::attr;
 
 pub fn expand_derive_de(input: DeriveInput) -> TokenStream {
     let struct_ident = input.ident;
+    let header = ImplHeader::new(&struct_ident, &input.generics);
     match input.data {
-        Data::Struct(data_struct) => expand_struct(struct_ident, data_struct),
-        Data::Enum(_) => unimplemented!(),
-        Data::Union(_) => unimplemented!(),
+        Data::Struct(data_struct) => expand_struct(header, data_struct),
+        Data::Enum(data_enum) => expand_enum(header, &struct_ident, data_enum),
+        Data::Union(data_union) => Err(syn::Error::new_spanned(
+            data_union.union_token,
+            "XDR derives don't support unions",
+        )),
     }
+    .unwrap_or_else(syn::Error::into_compile_error)
 }
 
-pub fn expand_struct(struct_ident: Ident, data_struct: DataStruct) -> TokenStream {
-    let fields_named = match data_struct.fields {
-        Fields::Named(fields_named) => fields_named,
-        Fields::Unnamed(_) | Fields::Unit => unreachable!(),
-    };
+/// Trait implementation and signature of the deserialize function.
+///
+/// Types without lifetimes implement `XdrDeserialize`. Types with lifetimes implement
+/// `XdrDeserializeBorrowed<'de>` instead, so that their fields can borrow from the input with
+/// every lifetime outlived by `'de`. Type parameters are required to implement the same trait.
+struct ImplHeader {
+    borrowed: bool,
+    header: TokenStream,
+}
 
-    let deserializations = fields_named
-        .named
-        .iter()
-        .map(|field| {
-            let ident = &field.ident;
+impl ImplHeader {
+    fn new(ident: &Ident, generics: &Generics) -> Self {
+        let borrowed = generics.lifetimes().next().is_some();
+        let mut impl_generics = generics.clone();
+        let (trait_path, bound) = if borrowed {
+            let lifetimes = generics.lifetimes().map(|def| &def.lifetime);
+            impl_generics
+                .params
+                .insert(0, parse_quote!('de: #(#lifetimes)+*));
+            (
+                quote!(::rpc_lib::XdrDeserializeBorrowed<'de>),
+                quote!(::rpc_lib::XdrDeserializeBorrowed<'de>),
+            )
+        } else {
+            (quote!(XdrDeserialize), quote!(XdrDeserialize))
+        };
+        let where_clause = impl_generics.make_where_clause();
+        for param in &generics.params {
+            if let GenericParam::Type(param) = param {
+                let ident = &param.ident;
+                where_clause.predicates.push(parse_quote!(#ident: #bound));
+            }
+        }
+        if where_clause.predicates.is_empty() {
+            impl_generics.where_clause = None;
+        }
+
+        let (impl_generics, _, where_clause) = impl_generics.split_for_impl();
+        let (_, ty_generics, _) = generics.split_for_impl();
+        let header = quote!(impl #impl_generics #trait_path for #ident #ty_generics #where_clause);
+        ImplHeader { borrowed, header }
+    }
+
+    /// Implementation with the deserialize function. `uses_reader` is false if `body` doesn't
+    /// refer to the reader.
+    ///
+    /// Types implementing `XdrDeserialize` also get a `decode_from` function decoding from a
+    /// slice, with `slice_body` written for a `reader` of type `&mut &[u8]` that returns the value
+    /// through [`decoded_from_slice`].
+    fn implement(
+        &self,
+        body: TokenStream,
+        slice_body: TokenStream,
+        uses_reader: bool,
+    ) -> TokenStream {
+        let header = &self.header;
+        let signature = match (self.borrowed, uses_reader) {
+            (true, true) => {
+                quote!(fn deserialize_borrowed(reader: &mut &'de [u8]) -> ::std::io::Result<Self>)
+            }
+            (true, false) => {
+                quote!(fn deserialize_borrowed(_reader: &mut &'de [u8]) -> ::std::io::Result<Self>)
+            }
+            (false, true) => {
+                quote!(fn deserialize(mut reader: impl ::std::io::Read) -> ::std::io::Result<Self>)
+            }
+            (false, false) => {
+                quote!(fn deserialize(_reader: impl ::std::io::Read) -> ::std::io::Result<Self>)
+            }
+        };
+        // Borrowed types are decoded from slices anyway
+        let decode_from = if self.borrowed || !uses_reader {
+            quote!()
+        } else {
             quote! {
-                #ident: XdrDeserialize::deserialize(&mut reader)?,
+                fn decode_from(bytes: &[u8]) -> ::std::io::Result<(Self, usize)> {
+                    let reader = &mut &*bytes;
+                    #slice_body
+                }
             }
-        })
-        .collect::<TokenStream>();
+        };
+        quote! {
+            #header {
+                #signature {
+                    #body
+                }
 
-    quote! {
-        impl XdrDeserialize for #struct_ident {
-            fn deserialize(mut reader: impl ::std::io::Read) -> ::std::io::Result<Self> {
-                Ok(Self {
-                    #deserializations
-                })
+                #decode_from
             }
         }
     }
 }
 
+/// Mutable reference to the reader. With `slice`, the reader is a `&mut &[u8]`.
+fn reader(slice: bool) -> TokenStream {
+    if slice {
+        quote!(&mut *reader)
+    } else {
+        quote!(&mut reader)
+    }
+}
+
+/// Result of `decode_from`: the value and the number of bytes read from `bytes`.
+fn decoded_from_slice(value: TokenStream) -> TokenStream {
+    quote!(Ok((#value, bytes.len() - reader.len())))
+}
+
+/// Constructs `path` with the decoded fields. `first` replaces the value of the first field.
+/// With `slice`, the fields are decoded from a `&mut &[u8]` and may borrow from it.
+fn construct(
+    slice: bool,
+    path: TokenStream,
+    fields: &Fields,
+    first: Option<TokenStream>,
+) -> syn::Result<TokenStream> {
+    let reader = reader(slice);
+    let xdr_fields = attr::fields(fields)?;
+    let mut values: Vec<TokenStream> = xdr_fields
+        .iter()
+        .map(|field| field.deserialize(&reader, slice))
+        .collect();
+    if let Some(first) = first {
+        values[0] = first;
+    }
+    Ok(match fields {
+        Fields::Named(fields) => {
+            let names = fields.named.iter().map(|field| &field.ident);
+            quote!(#path { #(#names: #values,)* })
+        }
+        Fields::Unnamed(_) => quote!(#path(#(#values),*)),
+        Fields::Unit => path,
+    })
+}
+
+fn expand_struct(header: ImplHeader, data_struct: DataStruct) -> syn::Result<TokenStream> {
+    let value = construct(header.borrowed, quote!(Self), &data_struct.fields, None)?;
+    let slice_value = construct(true, quote!(Self), &data_struct.fields, None)?;
+    let uses_reader = !attr::fields(&data_struct.fields)?
+        .iter()
+        .all(attr::XdrField::skipped);
+
+    let decoded = decoded_from_slice(quote!(value));
+    let slice_body = quote! {
+        let value = #slice_value;
+        #decoded
+    };
+    Ok(header.implement(quote!(Ok(#value)), slice_body, uses_reader))
+}
+
+/// Decodes the discriminant and the fields of the matching variant. Unknown discriminants are
+/// passed to the `default` variant, or rejected if there is none.
+fn expand_enum(
+    header: ImplHeader,
+    enum_ident: &Ident,
+    data_enum: DataEnum,
+) -> syn::Result<TokenStream> {
+    let variants = attr::variants(&data_enum)?;
+    let body = enum_body(
+        &variants,
+        enum_ident,
+        header.borrowed,
+        |value| quote!(Ok(#value)),
+    )?;
+    let slice_body = enum_body(&variants, enum_ident, true, decoded_from_slice)?;
+    Ok(header.implement(body, slice_body, true))
+}
+
+/// Body decoding the discriminant and the matching variant, which is returned through `ret`.
+fn enum_body(
+    variants: &[attr::XdrVariant<'_>],
+    enum_ident: &Ident,
+    slice: bool,
+    ret: impl Fn(TokenStream) -> TokenStream,
+) -> syn::Result<TokenStream> {
+    let construct = |variant: &attr::XdrVariant<'_>| {
+        let ident = &variant.variant.ident;
+        let first = variant.default.then(|| quote!(discriminant));
+        construct(slice, quote!(Self::#ident), &variant.variant.fields, first)
+    };
+
+    let cases = variants
+        .iter()
+        .filter(|variant| !variant.default)
+        .map(|variant| {
+            let case = &variant.case;
+            let value = ret(construct(variant)?);
+            Ok(quote! {
+                if discriminant == #case {
+                    return #value;
+                }
+            })
+        })
+        .collect::<syn::Result<TokenStream>>()?;
+
+    let default = match variants.iter().find(|variant| variant.default) {
+        Some(variant) => ret(construct(variant)?),
+        None => {
+            let message = format!("invalid discriminant {{}} of {}", enum_ident);
+            quote! {
+                Err(::std::io::Error::new(
+                    ::std::io::ErrorKind::InvalidData,
+                    format!(#message, discriminant),
+                ))
+            }
+        }
+    };
+
+    let reader = reader(slice);
+    Ok(quote! {
+        let discriminant = i32::deserialize(#reader)?;
+        #cases
+        #default
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use quote::quote;
@@ -63,9 +259,262 @@ mod tests {
                         baz: XdrDeserialize::deserialize(&mut reader)?,
                     })
                 }
+
+                fn decode_from(bytes: &[u8]) -> ::std::io::Result<(Self, usize)> {
+                    let reader = &mut &*bytes;
+                    let value = Self {
+                        bar: ::rpc_lib::XdrDeserializeBorrowed::deserialize_borrowed(&mut *reader)?,
+                        baz: ::rpc_lib::XdrDeserializeBorrowed::deserialize_borrowed(&mut *reader)?,
+                    };
+                    Ok((value, bytes.len() - reader.len()))
+                }
+            }
+        };
+
+        assert_eq!(output.to_string(), expand_derive_de(input).to_string());
+    }
+
+    #[test]
+    fn test_enum() {
+        let input = parse_quote! {
+            enum Foo {
+                Bar = 1,
+                Baz,
+                #[xdr(value = -1)]
+                Qux,
+            }
+        };
+
+        let output = quote! {
+            impl XdrDeserialize for Foo {
+                fn deserialize(mut reader: impl ::std::io::Read) -> ::std::io::Result<Self> {
+                    let discriminant = i32::deserialize(&mut reader)?;
+                    if discriminant == 1 {
+                        return Ok(Self::Bar);
+                    }
+                    if discriminant == (1 + 1i32) {
+                        return Ok(Self::Baz);
+                    }
+                    if discriminant == -1 {
+                        return Ok(Self::Qux);
+                    }
+                    Err(::std::io::Error::new(
+                        ::std::io::ErrorKind::InvalidData,
+                        format!("invalid discriminant {} of Foo", discriminant),
+                    ))
+                }
+
+                fn decode_from(bytes: &[u8]) -> ::std::io::Result<(Self, usize)> {
+                    let reader = &mut &*bytes;
+                    let discriminant = i32::deserialize(&mut *reader)?;
+                    if discriminant == 1 {
+                        return Ok((Self::Bar, bytes.len() - reader.len()));
+                    }
+                    if discriminant == (1 + 1i32) {
+                        return Ok((Self::Baz, bytes.len() - reader.len()));
+                    }
+                    if discriminant == -1 {
+                        return Ok((Self::Qux, bytes.len() - reader.len()));
+                    }
+                    Err(::std::io::Error::new(
+                        ::std::io::ErrorKind::InvalidData,
+                        format!("invalid discriminant {} of Foo", discriminant),
+                    ))
+                }
+            }
+        };
+
+        assert_eq!(output.to_string(), expand_derive_de(input).to_string());
+    }
+
+    #[test]
+    fn test_union_default() {
+        let input = parse_quote! {
+            enum Foo {
+                Bar { bar: u32 },
+                #[xdr(default)]
+                Other { discriminant: i32, data: u32 },
+            }
+        };
+
+        let output = quote! {
+            impl XdrDeserialize for Foo {
+                fn deserialize(mut reader: impl ::std::io::Read) -> ::std::io::Result<Self> {
+                    let discriminant = i32::deserialize(&mut reader)?;
+                    if discriminant == 0i32 {
+                        return Ok(Self::Bar {
+                            bar: XdrDeserialize::deserialize(&mut reader)?,
+                        });
+                    }
+                    Ok(Self::Other {
+                        discriminant: discriminant,
+                        data: XdrDeserialize::deserialize(&mut reader)?,
+                    })
+                }
+
+                fn decode_from(bytes: &[u8]) -> ::std::io::Result<(Self, usize)> {
+                    let reader = &mut &*bytes;
+                    let discriminant = i32::deserialize(&mut *reader)?;
+                    if discriminant == 0i32 {
+                        return Ok((
+                            Self::Bar {
+                                bar: ::rpc_lib::XdrDeserializeBorrowed::deserialize_borrowed(
+                                    &mut *reader
+                                )?,
+                            },
+                            bytes.len() - reader.len()
+                        ));
+                    }
+                    Ok((
+                        Self::Other {
+                            discriminant: discriminant,
+                            data: ::rpc_lib::XdrDeserializeBorrowed::deserialize_borrowed(
+                                &mut *reader
+                            )?,
+                        },
+                        bytes.len() - reader.len()
+                    ))
+                }
+            }
+        };
+
+        assert_eq!(output.to_string(), expand_derive_de(input).to_string());
+    }
+
+    #[test]
+    fn test_tuple_attributes() {
+        let input = parse_quote! {
+            struct Foo(
+                #[xdr(skip)] u32,
+                #[xdr(with = "codec")] u32,
+                #[xdr(max_len = 8, opaque)] Box<[u8]>,
+            );
+        };
+
+        let output = quote! {
+            impl XdrDeserialize for Foo {
+                fn deserialize(mut reader: impl ::std::io::Read) -> ::std::io::Result<Self> {
+                    Ok(Self(
+                        ::std::default::Default::default(),
+                        codec::deserialize(&mut reader)?,
+                        ::std::convert::From::from(
+                            ::rpc_lib::deserialize_max_len::<::std::vec::Vec<u8> >(&mut reader, 8)?
+                        )
+                    ))
+                }
+
+                fn decode_from(bytes: &[u8]) -> ::std::io::Result<(Self, usize)> {
+                    let reader = &mut &*bytes;
+                    let value = Self(
+                        ::std::default::Default::default(),
+                        codec::deserialize(&mut *reader)?,
+                        ::std::convert::From::from(
+                            ::rpc_lib::deserialize_max_len::<::std::vec::Vec<u8> >(&mut *reader, 8)?
+                        )
+                    );
+                    Ok((value, bytes.len() - reader.len()))
+                }
+            }
+        };
+
+        assert_eq!(output.to_string(), expand_derive_de(input).to_string());
+    }
+
+    #[test]
+    fn test_unit() {
+        let input = parse_quote! {
+            struct Foo;
+        };
+
+        let output = quote! {
+            impl XdrDeserialize for Foo {
+                fn deserialize(_reader: impl ::std::io::Read) -> ::std::io::Result<Self> {
+                    Ok(Self)
+                }
+            }
+        };
+
+        assert_eq!(output.to_string(), expand_derive_de(input).to_string());
+    }
+
+    #[test]
+    fn test_generics() {
+        let input = parse_quote! {
+            struct Foo<T, U: Clone>
+            where
+                U: Default,
+            {
+                bar: T,
+                baz: U,
+            }
+        };
+
+        let output = quote! {
+            impl<T, U: Clone> XdrDeserialize for Foo<T, U>
+            where
+                U: Default,
+                T: XdrDeserialize,
+                U: XdrDeserialize
+            {
+                fn deserialize(mut reader: impl ::std::io::Read) -> ::std::io::Result<Self> {
+                    Ok(Self {
+                        bar: XdrDeserialize::deserialize(&mut reader)?,
+                        baz: XdrDeserialize::deserialize(&mut reader)?,
+                    })
+                }
+
+                fn decode_from(bytes: &[u8]) -> ::std::io::Result<(Self, usize)> {
+                    let reader = &mut &*bytes;
+                    let value = Self {
+                        bar: ::rpc_lib::XdrDeserializeBorrowed::deserialize_borrowed(&mut *reader)?,
+                        baz: ::rpc_lib::XdrDeserializeBorrowed::deserialize_borrowed(&mut *reader)?,
+                    };
+                    Ok((value, bytes.len() - reader.len()))
+                }
             }
         };
 
         assert_eq!(output.to_string(), expand_derive_de(input).to_string());
     }
+
+    #[test]
+    fn test_lifetimes() {
+        let input = parse_quote! {
+            struct Foo<'a, T> {
+                bar: &'a [u8],
+                baz: T,
+            }
+        };
+
+        let output = quote! {
+            impl<'de: 'a, 'a, T> ::rpc_lib::XdrDeserializeBorrowed<'de> for Foo<'a, T>
+            where
+                T: ::rpc_lib::XdrDeserializeBorrowed<'de>
+            {
+                fn deserialize_borrowed(reader: &mut &'de [u8]) -> ::std::io::Result<Self> {
+                    Ok(Self {
+                        bar: ::rpc_lib::XdrDeserializeBorrowed::deserialize_borrowed(&mut *reader)?,
+                        baz: ::rpc_lib::XdrDeserializeBorrowed::deserialize_borrowed(&mut *reader)?,
+                    })
+                }
+            }
+        };
+
+        assert_eq!(output.to_string(), expand_derive_de(input).to_string());
+    }
+
+    #[test]
+    fn test_union() {
+        let input = parse_quote! {
+            union Foo {
+                bar: u32,
+            }
+        };
+        let output = expand_derive_de(input).to_string();
+        assert!(
+            output.contains("XDR derives don't support unions"),
+            "{}",
+            output
+        );
+    }
 }
@@ -11,99 +11,129 @@
 use proc_macro::TokenStream;
 use syn::{parse_macro_input, DeriveInput};
 
-use std::fs::File;
-use std::io::prelude::*;
 use std::path::Path;
 
-use quote::{format_ident, quote};
+use quote::{quote, quote_spanned};
 
+mod attr;
 mod de;
-mod parser;
 mod ser;
 
 #[proc_macro_attribute]
 pub fn include_rpcl(meta: TokenStream, item: TokenStream) -> TokenStream {
     // Get Name of .x-File
-    let name_x_file: String = meta
+    let path_token = meta
         .into_iter()
         .next()
-        .expect("Invalid use of Macro: include_rpcl(<Filename>)")
-        .to_string();
+        .expect("Invalid use of Macro: include_rpcl(<Filename>)");
+    let path_span = proc_macro2::Span::from(path_token.span());
+    let name_x_file: String = path_token.to_string();
     let len = name_x_file.len();
     let path = Path::new(&name_x_file[1..len - 1]);
 
-    //Read .x-File
-    let mut file = File::open(path).expect("Couldn't open .x-File");
-    let mut s = String::new();
-    file.read_to_string(&mut s)
-        .unwrap_or_else(|_| panic!("Couldn't read {}", path.display()));
-    eprintln!("Parsing {}", path.display());
+    // Relative paths are resolved against the directory of the invoking crate's Cargo.toml
+    let full_path = match std::env::var_os("CARGO_MANIFEST_DIR") {
+        Some(manifest_dir) => Path::new(&manifest_dir).join(path),
+        None => path.to_path_buf(),
+    };
 
     //Extract Structname (struct <Name>;)
     let struct_name: String = item
+        .clone()
         .into_iter()
         .nth(1)
         .expect("Invalid Syntax: Must be: struct <Name>;")
         .to_string();
 
-    //Parsing
-    let (generated_code, prog_num, ver_num) = parser::parse(&s, &struct_name);
-
-    let name = format_ident!("{}", struct_name);
-    let doc_macro_call = std::format!("#[include_rpcl({})]", &name_x_file);
-    let common_code = quote! {
-
-        /// Contains connection to Rpc-Service and associated functions as defined in
-        #[doc = #name_x_file]
-        /// .
-        ///
-        /// # Examples
-        ///
-        /// Creates a connection to 127.0.0.1, makes an Rpc-Call and prints the result.
-        /// ```
-        /// use rpc_lib::include_rpcl;
-        ///
-        #[doc = #doc_macro_call]
-        /// struct RPCStruct;
-        ///
-        /// fn main() {
-        ///     let mut rpc = RPCStruct::new("127.0.0.1").expect("Server not available");
-        ///     let result = rpc.MY_RPC_PROCEDURE(&1, &2).expect("Rpc call failed");
-        ///     println!("MY_RPC_PROCEDURE returned: {}", result);
-        /// }
-        /// ```
-        struct #name {
-            client: rpc_lib::RpcClient
+    //Read .x-File
+    let s = match std::fs::read_to_string(&full_path) {
+        Ok(s) => s,
+        Err(err) => {
+            let message = format!("Couldn't read {}: {}", full_path.display(), err);
+            return quote_spanned!(path_span=> compile_error!(#message);).into();
         }
+    };
+    eprintln!("Parsing {}", path.display());
 
-        impl #name {
-            /// Creates Connection to requested Rpc-Service.
-            ///
-            /// Connects to Portmapper-Service, gets Port-Number of requested Rpc-Service and
-            /// connects to it.
-            fn new(address: &str) -> std::io::Result<#name> {
-                Ok(#name {
-                    client: rpc_lib::clnt_create(address.parse().unwrap(), #prog_num, #ver_num)?
-                })
+    //Parsing
+    let path = path.display().to_string();
+    let generated_code = match rpc_lib_build::generate_tokens(&s, &path, &struct_name) {
+        Ok(generated_code) => generated_code,
+        Err(errors) => {
+            let mut code = proc_macro2::TokenStream::from(item);
+            for err in errors {
+                code.extend(err.to_compile_error(&path, &s, path_span));
             }
+            return code.into();
         }
     };
 
+    // Makes cargo recompile the invoking crate when the .x-File changes
+    let full_path = full_path.to_string_lossy();
     let code = quote! {
-        #common_code
+        const _: &[u8] = include_bytes!(#full_path);
         #generated_code
     };
 
     code.into()
 }
 
-#[proc_macro_derive(XdrSerialize)]
+/// Implements `XdrSerialize` for a struct or an enum.
+///
+/// The fields of a struct are encoded in order, a unit struct is encoded like `void`. An enum is
+/// encoded as a discriminated union: the discriminant of the variant as `int`, followed by its
+/// fields. A fieldless enum is therefore encoded as an XDR enum. Discriminants follow the Rust
+/// discriminants, or are given with `#[xdr(value = N)]` or `#[xdr(case = N)]`. The variant marked
+/// with `#[xdr(default)]` is used for all other discriminants; its first field holds the
+/// discriminant, encoding it with the discriminant of another variant fails with
+/// [`std::io::ErrorKind::InvalidInput`].
+///
+/// ```ignore
+/// #[derive(XdrSerialize, XdrDeserialize)]
+/// enum Reply {
+///     #[xdr(case = 0)]
+///     Ok { value: i32 },
+///     #[xdr(case = 1)]
+///     NotFound,
+///     #[xdr(default)]
+///     Error(i32),
+/// }
+/// ```
+///
+/// Fields take the following attributes:
+///
+/// * `#[xdr(skip)]`: the field is not encoded and decoded as `Default::default()`
+/// * `#[xdr(with = "module")]`: the field is encoded with `module::len`, `module::serialize` and
+///   `module::deserialize`, which have the signatures of the trait functions
+/// * `#[xdr(max_len = N)]`: variable-length data with at most `N` elements, longer data fails to
+///   encode and decode
+/// * `#[xdr(opaque)]`: the field is encoded as variable-length opaque data, like `Vec<u8>`. Its
+///   type has to implement `AsRef<[u8]>` and `From<Vec<u8>>`
+#[proc_macro_derive(XdrSerialize, attributes(xdr))]
 pub fn xdr_ser(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     ser::expand_derive_ser(input).into()
 }
 
-#[proc_macro_derive(XdrDeserialize)]
+/// Implements `XdrDeserialize` for a struct or an enum, see [`XdrSerialize`](derive@XdrSerialize)
+/// for the encoding. Unknown discriminants are rejected with [`std::io::ErrorKind::InvalidData`],
+/// unless the enum has a `#[xdr(default)]` variant.
+///
+/// Type parameters are required to implement `XdrDeserialize`. Types with lifetime parameters
+/// implement `XdrDeserializeBorrowed<'de>` instead, so that fields like `&'a [u8]` or `&'a str`
+/// borrow from the decoded bytes.
+///
+/// ```ignore
+/// #[derive(XdrDeserialize)]
+/// struct File<'a> {
+///     name: &'a str,
+///     data: &'a [u8],
+/// }
+///
+/// let mut input = &bytes[..];
+/// let file = File::deserialize_borrowed(&mut input)?;
+/// ```
+#[proc_macro_derive(XdrDeserialize, attributes(xdr))]
 pub fn xdr_de(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     de::expand_derive_de(input).into()
@@ -1,60 +1,204 @@
 use proc_macro2::TokenStream;
 use quote::quote;
-use syn::{Data, DataStruct, DeriveInput, Fields, Generics, Ident};
+use syn::{parse_quote, Data, DataEnum, DataStruct, DeriveInput, Generics, Ident, Index, Member};
+
+use crate::attr;
+
+/// `impl` header for `XdrSerialize`, requiring type parameters to implement `XdrSerialize`.
+fn impl_header(ident: &Ident, generics: &Generics) -> TokenStream {
+    let mut impl_generics = generics.clone();
+    for param in impl_generics.type_params_mut() {
+        param.bounds.push(parse_quote!(XdrSerialize));
+    }
+    let (impl_generics, ty_generics, where_clause) = impl_generics.split_for_impl();
+    quote!(impl #impl_generics XdrSerialize for #ident #ty_generics #where_clause)
+}
 
 pub fn expand_derive_ser(input: DeriveInput) -> TokenStream {
     let struct_ident = input.ident;
     match input.data {
         Data::Struct(data_struct) => expand_struct(struct_ident, input.generics, data_struct),
-        Data::Enum(_) => unimplemented!(),
-        Data::Union(_) => unimplemented!(),
+        Data::Enum(data_enum) => expand_enum(struct_ident, input.generics, data_enum),
+        Data::Union(data_union) => Err(syn::Error::new_spanned(
+            data_union.union_token,
+            "XDR derives don't support unions",
+        )),
     }
+    .unwrap_or_else(syn::Error::into_compile_error)
 }
 
+/// Encodes the fields in order. Unit structs are encoded like `void`.
 pub fn expand_struct(
     struct_ident: Ident,
     generics: Generics,
     data_struct: DataStruct,
-) -> TokenStream {
-    let fields_named = match data_struct.fields {
-        Fields::Named(fields_named) => fields_named,
-        Fields::Unnamed(_) | Fields::Unit => unreachable!(),
-    };
-
-    let lengths = fields_named
-        .named
+) -> syn::Result<TokenStream> {
+    let header = impl_header(&struct_ident, &generics);
+    let fields = attr::fields(&data_struct.fields)?;
+    let members: Vec<Member> = data_struct
+        .fields
         .iter()
-        .map(|field| {
-            let field_ident = &field.ident;
-            quote! {
-                XdrSerialize::len(&self.#field_ident) +
-            }
+        .enumerate()
+        .map(|(i, field)| match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(Index::from(i)),
         })
+        .collect();
+
+    let lengths = fields
+        .iter()
+        .zip(&members)
+        .map(|(field, member)| field.len(quote!(&self.#member)))
         .collect::<TokenStream>();
 
-    let serializations = fields_named
-        .named
+    let serializations = fields
         .iter()
-        .map(|field| {
-            let field_ident = &field.ident;
-            quote! {
-                self.#field_ident.serialize(&mut writer)?;
-            }
-        })
+        .zip(&members)
+        .map(|(field, member)| field.serialize(quote!(self.#member), quote!(&self.#member)))
         .collect::<TokenStream>();
 
-    quote! {
-        impl #generics XdrSerialize for #struct_ident #generics {
+    let encodings = fields
+        .iter()
+        .zip(&members)
+        .map(|(field, member)| field.encode(quote!(self.#member), quote!(&self.#member)))
+        .collect::<TokenStream>();
+
+    // Without encoded fields, the default `encode_into` writes nothing as well
+    let (writer, encode_into) = if fields.iter().all(attr::XdrField::skipped) {
+        (quote!(_writer), quote!())
+    } else {
+        (
+            quote!(mut writer),
+            quote! {
+                fn encode_into(&self, buf: &mut [u8]) -> ::std::io::Result<usize> {
+                    let mut offset = 0;
+                    #encodings
+                    Ok(offset)
+                }
+            },
+        )
+    };
+
+    Ok(quote! {
+        #header {
             fn len(&self) -> usize {
                 #lengths 0
             }
 
-            fn serialize(&self, mut writer: impl ::std::io::Write) -> ::std::io::Result<()> {
+            fn serialize(&self, #writer: impl ::std::io::Write) -> ::std::io::Result<()> {
                 #serializations
                 Ok(())
             }
+
+            #encode_into
         }
+    })
+}
+
+/// Encodes the discriminant of the variant followed by its fields, like a discriminated union.
+/// Fieldless enums are thereby encoded as XDR enums.
+pub fn expand_enum(
+    enum_ident: Ident,
+    generics: Generics,
+    data_enum: DataEnum,
+) -> syn::Result<TokenStream> {
+    let header = impl_header(&enum_ident, &generics);
+    let variants = attr::variants(&data_enum)?;
+
+    // The default variant can't hold the discriminant of another variant, it would be decoded as
+    // that variant
+    let cases: Vec<&TokenStream> = variants
+        .iter()
+        .filter(|variant| !variant.default)
+        .map(|variant| &variant.case)
+        .collect();
+
+    let mut lengths = TokenStream::new();
+    let mut serializations = TokenStream::new();
+    let mut encodings = TokenStream::new();
+    for variant in &variants {
+        let fields = attr::fields(&variant.variant.fields)?;
+        let (idents, pattern) = variant.bindings();
+        let field_lengths = fields
+            .iter()
+            .zip(&idents)
+            .map(|(field, ident)| field.len(quote!(#ident)));
+        let field_serializations = fields
+            .iter()
+            .zip(&idents)
+            .map(|(field, ident)| field.serialize(quote!(#ident), quote!(#ident)));
+        let field_encodings = fields
+            .iter()
+            .zip(&idents)
+            .map(|(field, ident)| field.encode(quote!(#ident), quote!(#ident)));
+        let (discriminant_len, discriminant, discriminant_encoding) = if variant.default {
+            let check = if cases.is_empty() {
+                quote!()
+            } else {
+                let first = &idents[0];
+                let message = format!(
+                    "discriminant {{}} doesn't belong to {}::{}",
+                    enum_ident, variant.variant.ident
+                );
+                quote! {
+                    if #(*#first == #cases)||* {
+                        return Err(::std::io::Error::new(
+                            ::std::io::ErrorKind::InvalidInput,
+                            format!(#message, #first),
+                        ));
+                    }
+                }
+            };
+            (quote!(), check.clone(), check)
+        } else {
+            let case = &variant.case;
+            (
+                quote!(XdrSerialize::len(&0i32) +),
+                quote!(i32::serialize(&#case, &mut writer)?;),
+                quote!(offset += i32::encode_into(&#case, &mut buf[offset..])?;),
+            )
+        };
+        lengths.extend(quote! {
+            #pattern => #discriminant_len #(#field_lengths)* 0,
+        });
+        serializations.extend(quote! {
+            #pattern => {
+                #discriminant
+                #(#field_serializations)*
+            }
+        });
+        encodings.extend(quote! {
+            #pattern => {
+                #discriminant_encoding
+                #(#field_encodings)*
+            }
+        });
     }
+
+    Ok(quote! {
+        #header {
+            fn len(&self) -> usize {
+                match self {
+                    #lengths
+                }
+            }
+
+            fn serialize(&self, mut writer: impl ::std::io::Write) -> ::std::io::Result<()> {
+                match self {
+                    #serializations
+                }
+                Ok(())
+            }
+
+            fn encode_into(&self, buf: &mut [u8]) -> ::std::io::Result<usize> {
+                let mut offset = 0;
+                match self {
+                    #encodings
+                }
+                Ok(offset)
+            }
+        }
+    })
 }
 
 #[cfg(test)]
@@ -84,6 +228,13 @@ mod tests {
                     self.baz.serialize(&mut writer)?;
                     Ok(())
                 }
+
+                fn encode_into(&self, buf: &mut [u8]) -> ::std::io::Result<usize> {
+                    let mut offset = 0;
+                    offset += XdrSerialize::encode_into(&self.bar, &mut buf[offset..])?;
+                    offset += XdrSerialize::encode_into(&self.baz, &mut buf[offset..])?;
+                    Ok(offset)
+                }
             }
         };
 
@@ -110,9 +261,164 @@ mod tests {
                     self.baz.serialize(&mut writer)?;
                     Ok(())
                 }
+
+                fn encode_into(&self, buf: &mut [u8]) -> ::std::io::Result<usize> {
+                    let mut offset = 0;
+                    offset += XdrSerialize::encode_into(&self.bar, &mut buf[offset..])?;
+                    offset += XdrSerialize::encode_into(&self.baz, &mut buf[offset..])?;
+                    Ok(offset)
+                }
             }
         };
 
         assert_eq!(output.to_string(), expand_derive_ser(input).to_string());
     }
+
+    #[test]
+    fn test_enum() {
+        let input = parse_quote! {
+            enum Foo {
+                #[xdr(case = 2)]
+                Bar { bar: u32 },
+                Baz(u32, u32),
+                #[xdr(default)]
+                Other(i32),
+            }
+        };
+
+        let output = quote! {
+            impl XdrSerialize for Foo {
+                fn len(&self) -> usize {
+                    match self {
+                        Self::Bar { bar } => XdrSerialize::len(&0i32) + XdrSerialize::len(bar) + 0,
+                        Self::Baz(x0, x1) => XdrSerialize::len(&0i32)
+                            + XdrSerialize::len(x0)
+                            + XdrSerialize::len(x1)
+                            + 0,
+                        Self::Other(x0) => XdrSerialize::len(x0) + 0,
+                    }
+                }
+
+                fn serialize(&self, mut writer: impl ::std::io::Write) -> ::std::io::Result<()> {
+                    match self {
+                        Self::Bar { bar } => {
+                            i32::serialize(&2, &mut writer)?;
+                            bar.serialize(&mut writer)?;
+                        }
+                        Self::Baz(x0, x1) => {
+                            i32::serialize(&(2 + 1i32), &mut writer)?;
+                            x0.serialize(&mut writer)?;
+                            x1.serialize(&mut writer)?;
+                        }
+                        Self::Other(x0) => {
+                            if *x0 == 2 || *x0 == (2 + 1i32) {
+                                return Err(::std::io::Error::new(
+                                    ::std::io::ErrorKind::InvalidInput,
+                                    format!("discriminant {} doesn't belong to Foo::Other", x0),
+                                ));
+                            }
+                            x0.serialize(&mut writer)?;
+                        }
+                    }
+                    Ok(())
+                }
+
+                fn encode_into(&self, buf: &mut [u8]) -> ::std::io::Result<usize> {
+                    let mut offset = 0;
+                    match self {
+                        Self::Bar { bar } => {
+                            offset += i32::encode_into(&2, &mut buf[offset..])?;
+                            offset += XdrSerialize::encode_into(bar, &mut buf[offset..])?;
+                        }
+                        Self::Baz(x0, x1) => {
+                            offset += i32::encode_into(&(2 + 1i32), &mut buf[offset..])?;
+                            offset += XdrSerialize::encode_into(x0, &mut buf[offset..])?;
+                            offset += XdrSerialize::encode_into(x1, &mut buf[offset..])?;
+                        }
+                        Self::Other(x0) => {
+                            if *x0 == 2 || *x0 == (2 + 1i32) {
+                                return Err(::std::io::Error::new(
+                                    ::std::io::ErrorKind::InvalidInput,
+                                    format!("discriminant {} doesn't belong to Foo::Other", x0),
+                                ));
+                            }
+                            offset += XdrSerialize::encode_into(x0, &mut buf[offset..])?;
+                        }
+                    }
+                    Ok(offset)
+                }
+            }
+        };
+
+        assert_eq!(output.to_string(), expand_derive_ser(input).to_string());
+    }
+
+    #[test]
+    fn test_tuple_attributes() {
+        let input = parse_quote! {
+            struct Foo(
+                #[xdr(skip)] u32,
+                #[xdr(with = "codec")] u32,
+                #[xdr(max_len = 8, opaque)] Box<[u8]>,
+            );
+        };
+
+        let output = quote! {
+            impl XdrSerialize for Foo {
+                fn len(&self) -> usize {
+                    codec::len(&self.1)
+                        + XdrSerialize::len(::std::convert::AsRef::<[u8]>::as_ref(&self.2))
+                        + 0
+                }
+
+                fn serialize(&self, mut writer: impl ::std::io::Write) -> ::std::io::Result<()> {
+                    codec::serialize(&self.1, &mut writer)?;
+                    ::rpc_lib::serialize_max_len(
+                        ::std::convert::AsRef::<[u8]>::as_ref(&self.2),
+                        &mut writer,
+                        8
+                    )?;
+                    Ok(())
+                }
+
+                fn encode_into(&self, buf: &mut [u8]) -> ::std::io::Result<usize> {
+                    let mut offset = 0;
+                    {
+                        let mut writer = &mut buf[offset..];
+                        let available = writer.len();
+                        codec::serialize(&self.1, &mut writer)?;
+                        offset += available - writer.len();
+                    }
+                    {
+                        let mut writer = &mut buf[offset..];
+                        let available = writer.len();
+                        ::rpc_lib::serialize_max_len(
+                            ::std::convert::AsRef::<[u8]>::as_ref(&self.2),
+                            &mut writer,
+                            8
+                        )?;
+                        offset += available - writer.len();
+                    }
+                    Ok(offset)
+                }
+            }
+        };
+
+        assert_eq!(output.to_string(), expand_derive_ser(input).to_string());
+    }
+
+    #[test]
+    fn test_union() {
+        let input = parse_quote! {
+            union Foo {
+                bar: u32,
+            }
+        };
+        let output = expand_derive_ser(input).to_string();
+        assert!(
+            output.contains("XDR derives don't support unions"),
+            "{}",
+            output
+        );
+    }
 }
@@ -0,0 +1,174 @@
+// Copyright 2022 Philipp Fensch
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! # rpcgen-rs
+//!
+//! Generates Rust-code from an RPC-Definition in the ONC-RPC format with the same parser as
+//! `rpc_lib::include_rpcl`. The flags mirror `rpcgen`:
+//!
+//! * no flag: datatypes, client and server trait into `<file stem>.rs`
+//! * `-h`/`-c`: datatypes with their XDR-implementations
+//! * `-l`: client struct
+//! * `-m`: server trait with `dispatch` function
+//! * `-Ss`: implementation of the server trait to be filled in
+//! * `-Sc`: sample `main` calling the procedures
+//!
+//! With one of the flags, the code is written to stdout unless `-o` is given.
+#![forbid(unsafe_code)]
+#![warn(rust_2018_idioms)]
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{ArgAction, ArgGroup, Parser, ValueEnum};
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Sample {
+    /// Implementation of the server trait
+    #[value(name = "s")]
+    Server,
+    /// Sample main calling the procedures with the client
+    #[value(name = "c")]
+    Client,
+}
+
+#[derive(Debug, Parser)]
+#[command(version, about, disable_help_flag = true)]
+#[command(group(ArgGroup::new("mode").args(["types", "client", "server", "stubs", "sample_main", "sample"])))]
+struct Args {
+    /// Generate the datatypes and their XDR-implementations
+    #[arg(short = 'h', long, short_alias = 'c')]
+    types: bool,
+
+    /// Generate the client struct
+    #[arg(short = 'l', long)]
+    client: bool,
+
+    /// Generate the server trait
+    #[arg(short = 'm', long)]
+    server: bool,
+
+    /// Generate an implementation of the server trait to be filled in (same as -Ss)
+    #[arg(long)]
+    stubs: bool,
+
+    /// Generate a sample main calling the procedures (same as -Sc)
+    #[arg(long)]
+    sample_main: bool,
+
+    /// Generate sample code: `s` for server stubs, `c` for a client main
+    #[arg(short = 'S', value_enum)]
+    sample: Option<Sample>,
+
+    /// Write the code into this file
+    #[arg(short = 'o', value_name = "OUTFILE")]
+    output: Option<PathBuf>,
+
+    /// Name of the client struct, defaults to `<Program>Client`
+    #[arg(long)]
+    client_name: Option<String>,
+
+    /// Name of the server trait, defaults to `<Program>Server`
+    #[arg(long)]
+    server_name: Option<String>,
+
+    /// Map linked lists to `rpc_lib::XdrList`, a `Vec` of their elements
+    #[arg(long)]
+    lists_as_vec: bool,
+
+    /// Map strings to `rpc_lib::XdrString`, which holds arbitrary bytes instead of UTF-8
+    #[arg(long)]
+    strings_as_bytes: bool,
+
+    /// Print help
+    #[arg(long, action = ArgAction::Help)]
+    help: Option<bool>,
+
+    /// The .x-File
+    infile: PathBuf,
+}
+
+fn config(args: &Args) -> rpc_lib_build::Config {
+    let mut config = rpc_lib_build::Config::new();
+    if let Some(name) = &args.client_name {
+        config.client_name(name);
+    }
+    if let Some(name) = &args.server_name {
+        config.server_name(name);
+    }
+    config.linked_lists_as_vec(args.lists_as_vec);
+    config.strings_as_bytes(args.strings_as_bytes);
+    let stubs = args.stubs || matches!(args.sample, Some(Sample::Server));
+    let sample_main = args.sample_main || matches!(args.sample, Some(Sample::Client));
+    if args.types || args.client || args.server || stubs || sample_main {
+        config
+            .types(args.types)
+            .client(args.client)
+            .server(args.server)
+            .stubs(stubs)
+            .sample_main(sample_main);
+    } else {
+        config.server(true);
+    }
+    config
+}
+
+fn run(args: &Args) -> io::Result<()> {
+    let source = fs::read_to_string(&args.infile)?;
+    let code = config(args).generate_string(&source, &args.infile.display().to_string())?;
+
+    let single_mode = args.types
+        || args.client
+        || args.server
+        || args.stubs
+        || args.sample_main
+        || args.sample.is_some();
+    match &args.output {
+        Some(output) => fs::write(output, code),
+        None if single_mode => io::stdout().write_all(code.as_bytes()),
+        None => {
+            let stem = args.infile.file_stem().unwrap_or_default();
+            fs::write(PathBuf::from(stem).with_extension("rs"), code)
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("rpcgen-rs: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_args() {
+        use clap::CommandFactory;
+        Args::command().debug_assert();
+    }
+
+    #[test]
+    fn rpcgen_flags() {
+        let args = Args::try_parse_from(["rpcgen-rs", "-Ss", "test.x"]).unwrap();
+        assert!(matches!(args.sample, Some(Sample::Server)));
+        let args = Args::try_parse_from(["rpcgen-rs", "-h", "test.x"]).unwrap();
+        assert!(args.types);
+        let args = Args::try_parse_from(["rpcgen-rs", "-c", "test.x"]).unwrap();
+        assert!(args.types);
+        assert!(Args::try_parse_from(["rpcgen-rs", "-h", "-l", "test.x"]).is_err());
+    }
+}
@@ -17,7 +17,7 @@
 //! # Example
 //!
 //! Creates a connection to 127.0.0.1, makes an Rpc-Call and prints the result.
-//! ```rust
+//! ```rust,ignore
 //! use rpc_lib::include_rpcl;
 //!
 //! #[include_rpcl("my_rpcl_file.x")]
@@ -27,17 +27,23 @@
 //! let result = rpc.MY_RPC_PROCEDURE(&1, &2).expect("Rpc call failed");
 //! println!("MY_RPC_PROCEDURE returned: {}", result);
 //! ```
-#![forbid(unsafe_code)]
+#![deny(unsafe_code)]
 #![warn(rust_2018_idioms)]
 
+// Derived impls refer to `::rpc_lib`, also when used in this crate
+extern crate self as rpc_lib;
+
 mod rpc_struct;
 
 /// Reads file and generates Rustcode according to contents
 ///
+/// Relative paths are resolved against the directory containing the `Cargo.toml` of the invoking
+/// crate. The crate is rebuilt whenever the file changes.
+///
 /// # Examples
 /// Reads `my_file.x` and adds associated functions to `MyStruct` according to procedure-definitions in
 /// `my_file.x`
-/// ```
+/// ```ignore
 /// #[include_rpcl("my_file.x")]
 /// struct MyStruct;
 /// ```
@@ -48,4 +54,8 @@ pub use crate::rpc_struct::rpc_clnt::clnt_create;
 pub use crate::rpc_struct::rpc_clnt::RawResponseUnion;
 pub use crate::rpc_struct::rpc_clnt::RpcClient;
 
-pub use crate::rpc_struct::xdr::{XdrDeserialize, XdrSerialize};
+pub use crate::rpc_struct::xdr::{
+    check_allocation, deserialize_max_len, deserialize_option_discriminant, max_allocation,
+    serialize_max_len, set_max_allocation, DecodeContext, Quadruple, XdrDeserialize,
+    XdrDeserializeBorrowed, XdrList, XdrSerialize, XdrString, XdrUChar, DEFAULT_MAX_ALLOCATION,
+};
@@ -11,7 +11,7 @@ use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
 use std::net::{AddrParseError, IpAddr, SocketAddr, TcpStream};
 use std::str::FromStr;
 
-use crate::{XdrDeserialize, XdrSerialize};
+use crate::{deserialize_max_len, XdrDeserialize, XdrSerialize};
 
 #[derive(XdrSerialize, XdrDeserialize)]
 struct Rpcb {
@@ -176,7 +176,18 @@ impl RpcClient {
         args: impl XdrSerialize,
     ) -> io::Result<T> {
         self.send_request(procedure, args)?;
-        self.recv()
+        self.recv(|reader| T::deserialize(reader))
+    }
+
+    /// Makes a RPC call whose result is variable-length data with at most `max_len` elements.
+    pub fn call_max_len<T: XdrDeserialize>(
+        &mut self,
+        procedure: u32,
+        args: impl XdrSerialize,
+        max_len: usize,
+    ) -> io::Result<T> {
+        self.send_request(procedure, args)?;
+        self.recv(|reader| deserialize_max_len(reader, max_len))
     }
 
     /// Makes a RPC call. Doesn't processes the response but writes it into `resp`.
@@ -207,18 +218,24 @@ impl RpcClient {
         let length = request.len() + args.len();
         let fragment_header = FragmentHeader::new(true, length.try_into().unwrap());
 
-        fragment_header.serialize(&mut self.writer)?;
-        request.serialize(&mut self.writer)?;
-        args.serialize(&mut self.writer)?;
+        // Encoded into one buffer, so that the writer is called once instead of once per field
+        let mut buf = vec![0; XdrSerialize::len(&fragment_header) + length];
+        let mut offset = fragment_header.encode_into(&mut buf)?;
+        offset += request.encode_into(&mut buf[offset..])?;
+        args.encode_into(&mut buf[offset..])?;
+        self.writer.write_all(&buf)?;
         self.writer.flush()?;
 
         Ok(())
     }
 
-    fn recv<T: XdrDeserialize>(&mut self) -> io::Result<T> {
+    fn recv<T>(
+        &mut self,
+        deserialize: impl FnOnce(&mut dyn Read) -> io::Result<T>,
+    ) -> io::Result<T> {
         let mut reader = FragmentReader::new(&mut self.reader);
         let _rpc_reply = RpcReply::deserialize(&mut reader)?;
-        XdrDeserialize::deserialize(&mut reader)
+        deserialize(&mut reader)
     }
 
     fn recv_raw_union<'a>(&mut self, target: &'a mut RawResponseUnion<'a, i32>) -> io::Result<()> {
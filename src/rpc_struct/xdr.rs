@@ -6,9 +6,16 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::convert::TryInto;
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::hash::{BuildHasher, Hash};
 use std::io::{self, Read, Write};
-use std::mem;
+use std::mem::{self, MaybeUninit};
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::vec::Vec;
 
 /// A data structure that can be serialized into the XDR format as described in [`RFC 4506`].
@@ -21,6 +28,53 @@ pub trait XdrSerialize {
 
     /// Serialize this value into the given writer.
     fn serialize(&self, writer: impl Write) -> io::Result<()>;
+
+    /// Encodes this value into the start of `buf` and returns the number of bytes written. Fails
+    /// with [`io::ErrorKind::WriteZero`] if `buf` is shorter than [`XdrSerialize::len`].
+    ///
+    /// Primitives, arrays and derived types write into the slice directly instead of issuing a
+    /// `Write` call per field. Other types fall back to [`XdrSerialize::serialize`].
+    fn encode_into(&self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = buf.len();
+        let mut writer = buf;
+        self.serialize(&mut writer)?;
+        Ok(available - writer.len())
+    }
+
+    /// Encodes this value into a new buffer of [`XdrSerialize::len`] bytes.
+    fn serialize_to_vec(&self) -> io::Result<Vec<u8>> {
+        let mut buf = vec![0; self.len()];
+        let written = self.encode_into(&mut buf)?;
+        buf.truncate(written);
+        Ok(buf)
+    }
+
+    /// Serializes the elements of an array one after another.
+    ///
+    /// Numeric types convert whole chunks of the slice at once instead of writing every element
+    /// separately.
+    fn serialize_slice(items: &[Self], mut writer: impl Write) -> io::Result<()>
+    where
+        Self: Sized,
+    {
+        for item in items {
+            item.serialize(&mut writer)?;
+        }
+        Ok(())
+    }
+
+    /// Encodes the elements of an array one after another into `buf`, like
+    /// [`XdrSerialize::encode_into`].
+    fn encode_slice_into(items: &[Self], buf: &mut [u8]) -> io::Result<usize>
+    where
+        Self: Sized,
+    {
+        let mut offset = 0;
+        for item in items {
+            offset += item.encode_into(&mut buf[offset..])?;
+        }
+        Ok(offset)
+    }
 }
 
 /// A data structure that can be deserialized from the XDR format as described in [`RFC 4506`].
@@ -29,176 +83,1444 @@ pub trait XdrSerialize {
 pub trait XdrDeserialize: Sized {
     /// Deserialize this value from the given reader.
     fn deserialize(reader: impl Read) -> io::Result<Self>;
+
+    /// Decodes a value from the start of `bytes` and returns it with the number of bytes read.
+    /// Fails with [`io::ErrorKind::UnexpectedEof`] if `bytes` ends early.
+    ///
+    /// Primitives, arrays and derived types read from the slice directly instead of going through
+    /// `Read`. Other types fall back to [`XdrDeserialize::deserialize`].
+    fn decode_from(bytes: &[u8]) -> io::Result<(Self, usize)> {
+        let mut reader = bytes;
+        let value = Self::deserialize(&mut reader)?;
+        Ok((value, bytes.len() - reader.len()))
+    }
+
+    /// Deserializes `len` elements of an array and appends them to `items`.
+    ///
+    /// Numeric types read and convert whole chunks at once instead of every element separately.
+    fn deserialize_slice(
+        items: &mut Vec<Self>,
+        len: usize,
+        mut reader: impl Read,
+    ) -> io::Result<()> {
+        for _ in 0..len {
+            items.push(Self::deserialize(&mut reader)?);
+        }
+        Ok(())
+    }
+
+    /// Decodes `len` elements of an array from the start of `bytes` and appends them to `items`,
+    /// like [`XdrDeserialize::decode_from`]. Returns the number of bytes read.
+    fn decode_slice_from(items: &mut Vec<Self>, len: usize, bytes: &[u8]) -> io::Result<usize> {
+        let mut offset = 0;
+        for _ in 0..len {
+            let (item, read) = Self::decode_from(&bytes[offset..])?;
+            items.push(item);
+            offset += read;
+        }
+        Ok(offset)
+    }
+}
+
+/// A data structure that can be deserialized from XDR data in memory, borrowing from it.
+///
+/// Opaque data and strings can be decoded as `&'de [u8]` and `&'de str` without copying them.
+/// Every type implementing [`XdrDeserialize`] implements this trait as well. `reader` is advanced
+/// past the decoded data.
+pub trait XdrDeserializeBorrowed<'de>: Sized {
+    /// Deserialize this value from the start of `reader`.
+    fn deserialize_borrowed(reader: &mut &'de [u8]) -> io::Result<Self>;
+}
+
+impl<'de, T: XdrDeserialize> XdrDeserializeBorrowed<'de> for T {
+    #[inline]
+    fn deserialize_borrowed(reader: &mut &'de [u8]) -> io::Result<Self> {
+        let (value, read) = T::decode_from(reader)?;
+        *reader = &reader[read..];
+        Ok(value)
+    }
+}
+
+fn padding(len: usize) -> usize {
+    (4 - len % 4) % 4
+}
+
+/// Copies `bytes` to the start of `buf` and returns their length.
+fn put(buf: &mut [u8], bytes: &[u8]) -> io::Result<usize> {
+    buf.get_mut(..bytes.len())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::WriteZero, "buffer too small"))?
+        .copy_from_slice(bytes);
+    Ok(bytes.len())
+}
+
+/// Returns the first `N` bytes of `bytes`.
+fn take<const N: usize>(bytes: &[u8]) -> io::Result<[u8; N]> {
+    bytes
+        .first_chunk()
+        .copied()
+        .ok_or_else(|| io::ErrorKind::UnexpectedEof.into())
+}
+
+/// Returns the first `len` bytes of `bytes`.
+fn take_slice(bytes: &[u8], len: usize) -> io::Result<&[u8]> {
+    bytes
+        .get(..len)
+        .ok_or_else(|| io::ErrorKind::UnexpectedEof.into())
+}
+
+/// Encodes opaque data and its padding into `buf`.
+fn put_opaque(buf: &mut [u8], bytes: &[u8]) -> io::Result<usize> {
+    let written = put(buf, bytes)?;
+    Ok(written + put(&mut buf[written..], &[0u8; 3][..padding(bytes.len())])?)
+}
+
+/// Decodes `len` bytes of opaque data and checks their padding. Returns the data and the number
+/// of bytes read including the padding.
+fn take_opaque(bytes: &[u8], len: usize) -> io::Result<(&[u8], usize)> {
+    let padded = len.saturating_add(padding(len));
+    let (data, padding_bytes) = take_slice(bytes, padded)?.split_at(len);
+    check_padding(padding_bytes)?;
+    Ok((data, padded))
+}
+
+/// Length of variable-length data, which has to fit into an unsigned int.
+fn encode_len(len: usize) -> io::Result<u32> {
+    u32::try_from(len).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("length {} doesn't fit into an unsigned int", len),
+        )
+    })
+}
+
+/// Returns true if optional data is followed by a value.
+pub fn deserialize_option_discriminant(reader: impl Read) -> io::Result<bool> {
+    option_discriminant(u32::deserialize(reader)?)
+}
+
+fn option_discriminant(discriminant: u32) -> io::Result<bool> {
+    match discriminant {
+        0 => Ok(false),
+        1 => Ok(true),
+        discriminant => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid discriminant {} of optional data", discriminant),
+        )),
+    }
+}
+
+/// Default of [`max_allocation`]: 64 MiB
+pub const DEFAULT_MAX_ALLOCATION: usize = 64 << 20;
+
+static MAX_ALLOCATION: AtomicUsize = AtomicUsize::new(DEFAULT_MAX_ALLOCATION);
+
+/// Maximum number of bytes that are allocated for a single variable-length array, opaque data or
+/// string while deserializing. Applies to data with and without a declared maximum length, so that
+/// a peer can't make us allocate arbitrary amounts of memory by sending a large length.
+pub fn max_allocation() -> usize {
+    MAX_ALLOCATION.load(Ordering::Relaxed)
+}
+
+/// Sets [`max_allocation`] for all following deserializations.
+pub fn set_max_allocation(bytes: usize) {
+    MAX_ALLOCATION.store(bytes, Ordering::Relaxed);
+}
+
+/// Limits of the current [`DecodeContext`]
+#[derive(Clone, Copy)]
+struct DecodeLimits {
+    max_allocation: usize,
+    remaining: usize,
+    strict_padding: bool,
+}
+
+thread_local! {
+    static DECODE_LIMITS: Cell<Option<DecodeLimits>> = const { Cell::new(None) };
+}
+
+/// Settings for deserializing untrusted input
+///
+/// Deserialization through [`DecodeContext::deserialize`] charges every allocation for
+/// variable-length data against a byte budget and fails once it is used up, so that the total
+/// memory of a decoded value is bounded. Without a context, only [`max_allocation`] applies and
+/// padding is checked strictly.
+///
+/// ```
+/// use rpc_lib::{DecodeContext, XdrDeserialize};
+///
+/// let bytes = [0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2];
+/// let mut context = DecodeContext::new();
+/// context.budget(4);
+/// assert!(context.deserialize::<Vec<u32>>(&bytes[..]).is_err());
+/// context.budget(8);
+/// assert_eq!(context.deserialize::<Vec<u32>>(&bytes[..]).unwrap(), [1, 2]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct DecodeContext {
+    max_allocation: usize,
+    budget: usize,
+    strict_padding: bool,
+}
+
+impl Default for DecodeContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DecodeContext {
+    /// Creates a context with the global [`max_allocation`], an unlimited budget and strict
+    /// padding.
+    pub fn new() -> Self {
+        Self {
+            max_allocation: max_allocation(),
+            budget: usize::MAX,
+            strict_padding: true,
+        }
+    }
+
+    /// Maximum number of bytes for a single variable-length array, opaque data or string
+    pub fn max_allocation(&mut self, bytes: usize) -> &mut Self {
+        self.max_allocation = bytes;
+        self
+    }
+
+    /// Maximum number of bytes for all variable-length data of a decoded value together
+    pub fn budget(&mut self, bytes: usize) -> &mut Self {
+        self.budget = bytes;
+        self
+    }
+
+    /// Whether padding of opaque data and strings has to be zero, as required by RFC 4506.
+    /// Defaults to true.
+    pub fn strict_padding(&mut self, strict: bool) -> &mut Self {
+        self.strict_padding = strict;
+        self
+    }
+
+    /// Deserializes a value with the limits of this context.
+    pub fn deserialize<T: XdrDeserialize>(&self, reader: impl Read) -> io::Result<T> {
+        self.with_limits(|| T::deserialize(reader))
+    }
+
+    /// Deserializes a value that may borrow from `reader` with the limits of this context.
+    pub fn deserialize_borrowed<'de, T: XdrDeserializeBorrowed<'de>>(
+        &self,
+        reader: &mut &'de [u8],
+    ) -> io::Result<T> {
+        self.with_limits(|| T::deserialize_borrowed(reader))
+    }
+
+    fn with_limits<T>(&self, f: impl FnOnce() -> T) -> T {
+        /// Restores the limits of an enclosing context, even when `f` panics.
+        struct Restore(Option<DecodeLimits>);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                DECODE_LIMITS.with(|limits| limits.set(self.0));
+            }
+        }
+
+        let limits = DecodeLimits {
+            max_allocation: self.max_allocation,
+            remaining: self.budget,
+            strict_padding: self.strict_padding,
+        };
+        let _restore = Restore(DECODE_LIMITS.with(|current| current.replace(Some(limits))));
+        f()
+    }
+}
+
+/// Checks that `len` elements of `T` fit into the maximum allocation and charges them to the
+/// budget of the current [`DecodeContext`].
+pub fn check_allocation<T>(len: usize) -> io::Result<()> {
+    let bytes = len.saturating_mul(mem::size_of::<T>());
+    let limits = DECODE_LIMITS.with(Cell::get);
+    let max_allocation = limits.map_or_else(max_allocation, |limits| limits.max_allocation);
+    if bytes > max_allocation {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "length {} exceeds the maximum allocation of {} bytes",
+                len, max_allocation
+            ),
+        ));
+    }
+    if let Some(mut limits) = limits {
+        if bytes > limits.remaining {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("length {} exceeds the decode budget", len),
+            ));
+        }
+        limits.remaining -= bytes;
+        DECODE_LIMITS.with(|current| current.set(Some(limits)));
+    }
+    Ok(())
+}
+
+/// Checks the padding after `len` bytes of opaque data or a string, which has to be zero unless
+/// the current [`DecodeContext`] allows otherwise.
+fn check_padding(padding: &[u8]) -> io::Result<()> {
+    let strict = DECODE_LIMITS
+        .with(Cell::get)
+        .is_none_or(|limits| limits.strict_padding);
+    if strict && padding.iter().any(|&byte| byte != 0) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "non-zero padding",
+        ));
+    }
+    Ok(())
+}
+
+/// Reads and checks the padding after `len` bytes of opaque data or a string.
+fn read_padding(mut reader: impl Read, len: usize) -> io::Result<()> {
+    let mut padding_bytes = [0u8; 3];
+    let padding_bytes = &mut padding_bytes[..padding(len)];
+    reader.read_exact(padding_bytes)?;
+    check_padding(padding_bytes)
+}
+
+fn length_exceeded(len: u32, max_len: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("length {} exceeds the maximum of {}", len, max_len),
+    )
+}
+
+/// Writer that checks the length of variable-length data before passing it on.
+struct MaxLenWriter<W> {
+    writer: W,
+    len: [u8; 4],
+    filled: usize,
+    max_len: usize,
+}
+
+impl<W: Write> Write for MaxLenWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.filled == self.len.len() {
+            return self.writer.write(buf);
+        }
+        let n = buf.len().min(self.len.len() - self.filled);
+        self.len[self.filled..self.filled + n].copy_from_slice(&buf[..n]);
+        self.filled += n;
+        if self.filled == self.len.len() {
+            let len = u32::from_be_bytes(self.len);
+            if len as usize > self.max_len {
+                return Err(length_exceeded(len, self.max_len));
+            }
+            self.writer.write_all(&self.len)?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Serializes variable-length data (arrays, opaque data and strings) with at most `max_len`
+/// elements. Fails with [`io::ErrorKind::InvalidData`] before anything is written if `value` is
+/// longer.
+pub fn serialize_max_len<T: XdrSerialize + ?Sized>(
+    value: &T,
+    writer: impl Write,
+    max_len: usize,
+) -> io::Result<()> {
+    value.serialize(MaxLenWriter {
+        writer,
+        len: [0; 4],
+        filled: 0,
+        max_len,
+    })
+}
+
+/// Deserializes variable-length data (arrays, opaque data and strings) with at most `max_len`
+/// elements. The length is checked before the data is read.
+pub fn deserialize_max_len<T: XdrDeserialize>(
+    mut reader: impl Read,
+    max_len: usize,
+) -> io::Result<T> {
+    let len = u32::deserialize(&mut reader)?;
+    if len as usize > max_len {
+        return Err(length_exceeded(len, max_len));
+    }
+    T::deserialize((&len.to_be_bytes()[..]).chain(reader))
+}
+
+impl XdrSerialize for () {
+    fn len(&self) -> usize {
+        0
+    }
+
+    fn serialize(&self, _writer: impl Write) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<T: XdrSerialize + ?Sized> XdrSerialize for &T {
+    #[inline]
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+
+    #[inline]
+    fn serialize(&self, writer: impl Write) -> io::Result<()> {
+        (**self).serialize(writer)
+    }
+
+    #[inline]
+    fn encode_into(&self, buf: &mut [u8]) -> io::Result<usize> {
+        (**self).encode_into(buf)
+    }
+}
+
+/// Structs without a name, encoded like a struct with the elements as fields
+macro_rules! impl_xdr_tuple {
+    ($($T:ident $index:tt),+) => {
+        impl<$($T: XdrSerialize),+> XdrSerialize for ($($T,)+) {
+            fn len(&self) -> usize {
+                0 $(+ self.$index.len())+
+            }
+
+            fn serialize(&self, mut writer: impl Write) -> io::Result<()> {
+                $(self.$index.serialize(&mut writer)?;)+
+                Ok(())
+            }
+
+            fn encode_into(&self, buf: &mut [u8]) -> io::Result<usize> {
+                let mut offset = 0;
+                $(offset += self.$index.encode_into(&mut buf[offset..])?;)+
+                Ok(offset)
+            }
+        }
+
+        impl<$($T: XdrDeserialize),+> XdrDeserialize for ($($T,)+) {
+            fn deserialize(mut reader: impl Read) -> io::Result<Self> {
+                Ok(($($T::deserialize(&mut reader)?,)+))
+            }
+
+            fn decode_from(bytes: &[u8]) -> io::Result<(Self, usize)> {
+                let mut offset = 0;
+                let value = ($({
+                    let (item, read) = $T::decode_from(&bytes[offset..])?;
+                    offset += read;
+                    item
+                },)+);
+                Ok((value, offset))
+            }
+        }
+    };
+}
+
+impl_xdr_tuple!(A 0);
+impl_xdr_tuple!(A 0, B 1);
+impl_xdr_tuple!(A 0, B 1, C 2);
+impl_xdr_tuple!(A 0, B 1, C 2, D 3);
+impl_xdr_tuple!(A 0, B 1, C 2, D 3, E 4);
+impl_xdr_tuple!(A 0, B 1, C 2, D 3, E 4, F 5);
+impl_xdr_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+impl_xdr_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+impl_xdr_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8);
+impl_xdr_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9);
+impl_xdr_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10);
+impl_xdr_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11);
+
+/// Smart pointers, encoded like the value they point to
+macro_rules! impl_xdr_pointer {
+    ($Ptr:ident) => {
+        impl<T: XdrSerialize + ?Sized> XdrSerialize for $Ptr<T> {
+            #[inline]
+            fn len(&self) -> usize {
+                (**self).len()
+            }
+
+            #[inline]
+            fn serialize(&self, writer: impl Write) -> io::Result<()> {
+                (**self).serialize(writer)
+            }
+
+            #[inline]
+            fn encode_into(&self, buf: &mut [u8]) -> io::Result<usize> {
+                (**self).encode_into(buf)
+            }
+        }
+
+        impl<T: XdrDeserialize> XdrDeserialize for $Ptr<T> {
+            #[inline]
+            fn deserialize(reader: impl Read) -> io::Result<Self> {
+                T::deserialize(reader).map($Ptr::new)
+            }
+
+            #[inline]
+            fn decode_from(bytes: &[u8]) -> io::Result<(Self, usize)> {
+                T::decode_from(bytes).map(|(value, read)| ($Ptr::new(value), read))
+            }
+        }
+    };
+}
+
+impl_xdr_pointer!(Box);
+impl_xdr_pointer!(Rc);
+impl_xdr_pointer!(Arc);
+
+/// Encoded like the borrowed value, e.g. `Cow<'_, [u8]>` as opaque data and `Cow<'_, str>` as a
+/// string. Always deserialized as [`Cow::Owned`].
+impl<B: XdrSerialize + ToOwned + ?Sized> XdrSerialize for Cow<'_, B> {
+    #[inline]
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+
+    #[inline]
+    fn serialize(&self, writer: impl Write) -> io::Result<()> {
+        (**self).serialize(writer)
+    }
+}
+
+impl<B: ToOwned + ?Sized> XdrDeserialize for Cow<'_, B>
+where
+    B::Owned: XdrDeserialize,
+{
+    #[inline]
+    fn deserialize(reader: impl Read) -> io::Result<Self> {
+        B::Owned::deserialize(reader).map(Cow::Owned)
+    }
+}
+
+/// Optional-Data
+///
+/// Encoded like a union with a boolean discriminant: `FALSE` for `None`, `TRUE` followed by the
+/// value for `Some`.
+///
+/// The value is encoded through `dyn Write` and decoded through `dyn Read`. Self-referential types
+/// like linked lists would otherwise instantiate their functions with infinitely nested reader and
+/// writer types.
+impl<T: XdrSerialize> XdrSerialize for Option<T> {
+    fn len(&self) -> usize {
+        match self {
+            Some(value) => 1u32.len() + value.len(),
+            None => 0u32.len(),
+        }
+    }
+
+    fn serialize(&self, mut writer: impl Write) -> io::Result<()> {
+        match self {
+            Some(value) => {
+                1u32.serialize(&mut writer)?;
+                value.serialize(&mut writer as &mut dyn Write)
+            }
+            None => 0u32.serialize(writer),
+        }
+    }
+
+    fn encode_into(&self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Some(value) => {
+                let written = 1u32.encode_into(buf)?;
+                Ok(written + value.encode_into(&mut buf[written..])?)
+            }
+            None => 0u32.encode_into(buf),
+        }
+    }
+}
+
+impl<T: XdrDeserialize> XdrDeserialize for Option<T> {
+    fn deserialize(mut reader: impl Read) -> io::Result<Self> {
+        if deserialize_option_discriminant(&mut reader)? {
+            T::deserialize(&mut reader as &mut dyn Read).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn decode_from(bytes: &[u8]) -> io::Result<(Self, usize)> {
+        let (discriminant, read) = u32::decode_from(bytes)?;
+        if option_discriminant(discriminant)? {
+            let (value, value_read) = T::decode_from(&bytes[read..])?;
+            Ok((Some(value), read + value_read))
+        } else {
+            Ok((None, read))
+        }
+    }
+}
+
+/// Linked list of optional data (`struct entry { ...; entry *next; };`) as a `Vec` of its elements.
+///
+/// Each element is preceded by `TRUE`, the end of the list is marked by `FALSE`, which is the same
+/// encoding as recursive optional data. The elements don't contain the `next` field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XdrList<T>(pub Vec<T>);
+
+impl<T> Default for XdrList<T> {
+    fn default() -> Self {
+        XdrList(Vec::new())
+    }
+}
+
+impl<T> Deref for XdrList<T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Vec<T> {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for XdrList<T> {
+    fn deref_mut(&mut self) -> &mut Vec<T> {
+        &mut self.0
+    }
+}
+
+impl<T> From<Vec<T>> for XdrList<T> {
+    fn from(vec: Vec<T>) -> Self {
+        XdrList(vec)
+    }
+}
+
+impl<T> From<XdrList<T>> for Vec<T> {
+    fn from(list: XdrList<T>) -> Self {
+        list.0
+    }
+}
+
+impl<T> FromIterator<T> for XdrList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        XdrList(iter.into_iter().collect())
+    }
+}
+
+impl<T> IntoIterator for XdrList<T> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<T: XdrSerialize> XdrSerialize for XdrList<T> {
+    fn len(&self) -> usize {
+        self.iter()
+            .map(|item| 1u32.len() + item.len())
+            .sum::<usize>()
+            + 0u32.len()
+    }
+
+    fn serialize(&self, mut writer: impl Write) -> io::Result<()> {
+        for item in self.iter() {
+            1u32.serialize(&mut writer)?;
+            item.serialize(&mut writer)?;
+        }
+        0u32.serialize(writer)
+    }
+}
+
+impl<T: XdrDeserialize> XdrDeserialize for XdrList<T> {
+    fn deserialize(mut reader: impl Read) -> io::Result<Self> {
+        let mut list = Vec::new();
+        while deserialize_option_discriminant(&mut reader)? {
+            check_allocation::<T>(1)?;
+            list.push(T::deserialize(&mut reader)?);
+        }
+        Ok(XdrList(list))
+    }
+}
+
+/// Fixed-Length Opaque Data
+///
+/// `u8` is reserved for opaque data, so `[u8; LEN]` is encoded as `LEN` bytes plus padding. Arrays
+/// of small integers, that are encoded as one 4-byte word per element (e.g. `unsigned char x[N]`),
+/// use `[XdrUChar; LEN]`, `[i8; LEN]` and the like.
+impl<const LEN: usize> XdrSerialize for [u8; LEN] {
+    fn len(&self) -> usize {
+        LEN + padding(LEN)
+    }
+
+    fn serialize(&self, mut writer: impl Write) -> io::Result<()> {
+        writer.write_all(self)?;
+        writer.write_all(&[0u8; 3][..padding(LEN)])
+    }
+
+    fn encode_into(&self, buf: &mut [u8]) -> io::Result<usize> {
+        put_opaque(buf, self)
+    }
+}
+
+impl<const LEN: usize> XdrDeserialize for [u8; LEN] {
+    fn deserialize(mut reader: impl Read) -> io::Result<Self> {
+        let mut this = [0; LEN];
+        reader.read_exact(&mut this)?;
+        read_padding(reader, LEN)?;
+        Ok(this)
+    }
+
+    fn decode_from(bytes: &[u8]) -> io::Result<(Self, usize)> {
+        let (data, read) = take_opaque(bytes, LEN)?;
+        let mut this = [0; LEN];
+        this.copy_from_slice(data);
+        Ok((this, read))
+    }
+}
+
+/// Variable-Length Opaque Data
+impl XdrSerialize for [u8] {
+    fn len(&self) -> usize {
+        (self.len() as u32).len() + self.len() + padding(self.len())
+    }
+
+    fn serialize(&self, mut writer: impl Write) -> io::Result<()> {
+        encode_len(self.len())?.serialize(&mut writer)?;
+        writer.write_all(self)?;
+        writer.write_all(&[0u8; 3][..padding(self.len())])
+    }
+
+    fn encode_into(&self, buf: &mut [u8]) -> io::Result<usize> {
+        let written = encode_len(self.len())?.encode_into(buf)?;
+        Ok(written + put_opaque(&mut buf[written..], self)?)
+    }
+}
+
+impl XdrSerialize for Vec<u8> {
+    fn len(&self) -> usize {
+        XdrSerialize::len(self.as_slice())
+    }
+
+    fn serialize(&self, writer: impl Write) -> io::Result<()> {
+        self.as_slice().serialize(writer)
+    }
+
+    fn encode_into(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.as_slice().encode_into(buf)
+    }
+}
+
+impl XdrDeserialize for Vec<u8> {
+    fn deserialize(mut reader: impl Read) -> io::Result<Self> {
+        let len = u32::deserialize(&mut reader)? as usize;
+        check_allocation::<u8>(len)?;
+        let mut this = vec![0; len];
+        reader.read_exact(&mut this)?;
+        read_padding(reader, len)?;
+        Ok(this)
+    }
+
+    fn decode_from(bytes: &[u8]) -> io::Result<(Self, usize)> {
+        let (len, read) = u32::decode_from(bytes)?;
+        check_allocation::<u8>(len as usize)?;
+        let (data, data_read) = take_opaque(&bytes[read..], len as usize)?;
+        Ok((data.to_vec(), read + data_read))
+    }
+}
+
+/// Variable-Length Opaque Data, borrowed from the input
+impl<'de> XdrDeserializeBorrowed<'de> for &'de [u8] {
+    fn deserialize_borrowed(reader: &mut &'de [u8]) -> io::Result<Self> {
+        let len = u32::deserialize(&mut *reader)? as usize;
+        let input: &'de [u8] = reader;
+        if input.len() < len.saturating_add(padding(len)) {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let (this, rest) = input.split_at(len);
+        let (padding_bytes, rest) = rest.split_at(padding(len));
+        check_padding(padding_bytes)?;
+        *reader = rest;
+        Ok(this)
+    }
+}
+
+/// String, borrowed from the input
+impl<'de> XdrDeserializeBorrowed<'de> for &'de str {
+    fn deserialize_borrowed(reader: &mut &'de [u8]) -> io::Result<Self> {
+        let bytes = <&[u8]>::deserialize_borrowed(reader)?;
+        std::str::from_utf8(bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+/// Fixed-Length Array
+impl<T: XdrSerialize, const LEN: usize> XdrSerialize for [T; LEN] {
+    fn len(&self) -> usize {
+        self.iter().map(|item| item.len()).sum()
+    }
+
+    fn serialize(&self, writer: impl Write) -> io::Result<()> {
+        T::serialize_slice(self, writer)
+    }
+
+    fn encode_into(&self, buf: &mut [u8]) -> io::Result<usize> {
+        T::encode_slice_into(self, buf)
+    }
+}
+
+impl<T: XdrDeserialize, const LEN: usize> XdrDeserialize for [T; LEN] {
+    fn deserialize(mut reader: impl Read) -> io::Result<Self> {
+        PartialArray::new().fill(|| T::deserialize(&mut reader))
+    }
+
+    fn decode_from(bytes: &[u8]) -> io::Result<(Self, usize)> {
+        let mut offset = 0;
+        let items = PartialArray::new().fill(|| {
+            let (item, read) = T::decode_from(&bytes[offset..])?;
+            offset += read;
+            Ok(item)
+        })?;
+        Ok((items, offset))
+    }
+}
+
+/// Fixed-length array that is decoded in place, element by element. If decoding fails, the
+/// elements decoded so far are dropped.
+struct PartialArray<T, const LEN: usize> {
+    items: [MaybeUninit<T>; LEN],
+    /// Number of leading elements that are initialized
+    initialized: usize,
+}
+
+#[allow(unsafe_code)]
+impl<T, const LEN: usize> PartialArray<T, LEN> {
+    fn new() -> Self {
+        PartialArray {
+            items: [const { MaybeUninit::uninit() }; LEN],
+            initialized: 0,
+        }
+    }
+
+    /// Decodes the elements in order with `decode`, stopping at the first error.
+    fn fill(mut self, mut decode: impl FnMut() -> io::Result<T>) -> io::Result<[T; LEN]> {
+        while self.initialized < LEN {
+            self.items[self.initialized].write(decode()?);
+            self.initialized += 1;
+        }
+        // The elements are moved out, so they must not be dropped with `self`
+        let this = mem::ManuallyDrop::new(self);
+        // SAFETY: All elements are initialized, and `MaybeUninit<T>` has the layout of `T`.
+        Ok(unsafe {
+            (&this.items as *const [MaybeUninit<T>; LEN])
+                .cast::<[T; LEN]>()
+                .read()
+        })
+    }
+}
+
+#[allow(unsafe_code)]
+impl<T, const LEN: usize> Drop for PartialArray<T, LEN> {
+    fn drop(&mut self) {
+        for item in &mut self.items[..self.initialized] {
+            // SAFETY: The first `initialized` elements are initialized.
+            unsafe { item.assume_init_drop() };
+        }
+    }
+}
+
+/// Variable-Length Array
+impl<T: XdrSerialize> XdrSerialize for [T] {
+    fn len(&self) -> usize {
+        (self.len() as u32).len() + self.iter().map(|item| item.len()).sum::<usize>()
+    }
+
+    fn serialize(&self, mut writer: impl Write) -> io::Result<()> {
+        encode_len(self.len())?.serialize(&mut writer)?;
+        T::serialize_slice(self, writer)
+    }
+
+    fn encode_into(&self, buf: &mut [u8]) -> io::Result<usize> {
+        let written = encode_len(self.len())?.encode_into(buf)?;
+        Ok(written + T::encode_slice_into(self, &mut buf[written..])?)
+    }
+}
+
+impl<T: XdrSerialize> XdrSerialize for Vec<T> {
+    fn len(&self) -> usize {
+        XdrSerialize::len(self.as_slice())
+    }
+
+    fn serialize(&self, writer: impl Write) -> io::Result<()> {
+        self.as_slice().serialize(writer)
+    }
+
+    fn encode_into(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.as_slice().encode_into(buf)
+    }
 }
 
-fn padding(len: usize) -> usize {
-    (4 - len % 4) % 4
+impl<T: XdrDeserialize> XdrDeserialize for Vec<T> {
+    fn deserialize(mut reader: impl Read) -> io::Result<Self> {
+        let len = u32::deserialize(&mut reader)? as usize;
+        check_allocation::<T>(len)?;
+        let mut this = Vec::with_capacity(len);
+        T::deserialize_slice(&mut this, len, reader)?;
+        Ok(this)
+    }
+
+    fn decode_from(bytes: &[u8]) -> io::Result<(Self, usize)> {
+        let (len, read) = u32::decode_from(bytes)?;
+        check_allocation::<T>(len as usize)?;
+        let mut this = Vec::with_capacity(len as usize);
+        let items_read = T::decode_slice_from(&mut this, len as usize, &bytes[read..])?;
+        Ok((this, read + items_read))
+    }
 }
 
-impl XdrSerialize for () {
+/// Variable-Length Array
+impl<T: XdrSerialize> XdrSerialize for VecDeque<T> {
     fn len(&self) -> usize {
-        0
+        0u32.len() + self.iter().map(|item| item.len()).sum::<usize>()
     }
 
-    fn serialize(&self, _writer: impl Write) -> io::Result<()> {
+    fn serialize(&self, mut writer: impl Write) -> io::Result<()> {
+        encode_len(self.len())?.serialize(&mut writer)?;
+        for item in self {
+            item.serialize(&mut writer)?;
+        }
         Ok(())
     }
 }
 
-impl<T: XdrSerialize + ?Sized> XdrSerialize for &T {
-    #[inline]
+impl<T: XdrDeserialize> XdrDeserialize for VecDeque<T> {
+    fn deserialize(reader: impl Read) -> io::Result<Self> {
+        Vec::deserialize(reader).map(VecDeque::from)
+    }
+}
+
+/// Serializes the entries of a map as a variable-length array of key-value pairs.
+fn serialize_map<'a, K: XdrSerialize + 'a, V: XdrSerialize + 'a>(
+    len: usize,
+    entries: impl Iterator<Item = (&'a K, &'a V)>,
+    mut writer: impl Write,
+) -> io::Result<()> {
+    encode_len(len)?.serialize(&mut writer)?;
+    for (key, value) in entries {
+        key.serialize(&mut writer)?;
+        value.serialize(&mut writer)?;
+    }
+    Ok(())
+}
+
+/// Deserializes a variable-length array of key-value pairs with `insert`, which returns true if
+/// the key is new. Duplicate keys are rejected.
+fn deserialize_map<K: XdrDeserialize, V: XdrDeserialize>(
+    mut reader: impl Read,
+    mut insert: impl FnMut(K, V) -> bool,
+) -> io::Result<()> {
+    let len = u32::deserialize(&mut reader)? as usize;
+    check_allocation::<(K, V)>(len)?;
+    for _ in 0..len {
+        let key = K::deserialize(&mut reader)?;
+        let value = V::deserialize(&mut reader)?;
+        if !insert(key, value) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "duplicate key in map",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Variable-length array of key-value pairs in the order of iteration
+impl<K: XdrSerialize, V: XdrSerialize, S> XdrSerialize for HashMap<K, V, S> {
     fn len(&self) -> usize {
-        (**self).len()
+        0u32.len()
+            + self
+                .iter()
+                .map(|(key, value)| key.len() + value.len())
+                .sum::<usize>()
     }
 
-    #[inline]
     fn serialize(&self, writer: impl Write) -> io::Result<()> {
-        (**self).serialize(writer)
+        serialize_map(self.len(), self.iter(), writer)
     }
 }
 
-/// Fixed-Length Opaque Data
-impl<const LEN: usize> XdrSerialize for [u8; LEN] {
+impl<K, V, S> XdrDeserialize for HashMap<K, V, S>
+where
+    K: XdrDeserialize + Eq + Hash,
+    V: XdrDeserialize,
+    S: BuildHasher + Default,
+{
+    fn deserialize(reader: impl Read) -> io::Result<Self> {
+        let mut map = HashMap::default();
+        deserialize_map(reader, |key, value| map.insert(key, value).is_none())?;
+        Ok(map)
+    }
+}
+
+/// Variable-length array of key-value pairs, ordered by key
+impl<K: XdrSerialize, V: XdrSerialize> XdrSerialize for BTreeMap<K, V> {
     fn len(&self) -> usize {
-        LEN + padding(LEN)
+        0u32.len()
+            + self
+                .iter()
+                .map(|(key, value)| key.len() + value.len())
+                .sum::<usize>()
     }
 
-    fn serialize(&self, mut writer: impl Write) -> io::Result<()> {
-        writer.write_all(self)?;
-        writer.write_all(&[0u8; 3][..padding(LEN)])
+    fn serialize(&self, writer: impl Write) -> io::Result<()> {
+        serialize_map(self.len(), self.iter(), writer)
     }
 }
 
-impl<const LEN: usize> XdrDeserialize for [u8; LEN] {
-    fn deserialize(mut reader: impl Read) -> io::Result<Self> {
-        let mut this = [0; LEN];
-        reader.read_exact(&mut this)?;
-        Ok(this)
+impl<K: XdrDeserialize + Ord, V: XdrDeserialize> XdrDeserialize for BTreeMap<K, V> {
+    fn deserialize(reader: impl Read) -> io::Result<Self> {
+        let mut map = BTreeMap::new();
+        deserialize_map(reader, |key, value| map.insert(key, value).is_none())?;
+        Ok(map)
     }
 }
 
 /// Variable-Length Opaque Data
-impl XdrSerialize for [u8] {
+#[cfg(feature = "bytes")]
+impl XdrSerialize for bytes::Bytes {
     fn len(&self) -> usize {
-        (self.len() as u32).len() + self.len() + padding(self.len())
+        XdrSerialize::len(&self[..])
     }
 
-    fn serialize(&self, mut writer: impl Write) -> io::Result<()> {
-        (self.len() as u32).serialize(&mut writer)?;
-        writer.write_all(self)?;
-        writer.write_all(&[0u8; 3][..padding(self.len())])
+    fn serialize(&self, writer: impl Write) -> io::Result<()> {
+        self[..].serialize(writer)
     }
 }
 
-impl XdrSerialize for Vec<u8> {
+#[cfg(feature = "bytes")]
+impl XdrDeserialize for bytes::Bytes {
+    fn deserialize(reader: impl Read) -> io::Result<Self> {
+        Vec::<u8>::deserialize(reader).map(bytes::Bytes::from)
+    }
+}
+
+/// String, encoded as its UTF-8 bytes
+impl XdrSerialize for str {
     fn len(&self) -> usize {
-        XdrSerialize::len(self.as_slice())
+        XdrSerialize::len(self.as_bytes())
     }
 
     fn serialize(&self, writer: impl Write) -> io::Result<()> {
-        self.as_slice().serialize(writer)
+        self.as_bytes().serialize(writer)
+    }
+
+    fn encode_into(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.as_bytes().encode_into(buf)
     }
 }
 
-impl XdrDeserialize for Vec<u8> {
-    fn deserialize(mut reader: impl Read) -> io::Result<Self> {
-        let len = u32::deserialize(&mut reader)? as usize;
-        let mut this = vec![0; len];
-        reader.read_exact(&mut this)?;
-        Ok(this)
+impl XdrSerialize for String {
+    fn len(&self) -> usize {
+        XdrSerialize::len(self.as_str())
+    }
+
+    fn serialize(&self, writer: impl Write) -> io::Result<()> {
+        self.as_str().serialize(writer)
+    }
+
+    fn encode_into(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.as_str().encode_into(buf)
     }
 }
 
-/// Fixed-Length Array
-impl<T: XdrSerialize, const LEN: usize> XdrSerialize for [T; LEN] {
+impl XdrDeserialize for String {
+    fn deserialize(reader: impl Read) -> io::Result<Self> {
+        let vec = Vec::<u8>::deserialize(reader)?;
+        Self::from_utf8(vec).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    fn decode_from(bytes: &[u8]) -> io::Result<(Self, usize)> {
+        let (vec, read) = Vec::<u8>::decode_from(bytes)?;
+        let this =
+            Self::from_utf8(vec).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Ok((this, read))
+    }
+}
+
+/// String of arbitrary bytes
+///
+/// XDR strings are sequences of bytes without an encoding. [`String`] requires UTF-8 and fails to
+/// decode anything else, like Latin-1 file names. `XdrString` keeps the bytes and converts them
+/// when needed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct XdrString(pub Vec<u8>);
+
+impl XdrString {
+    /// Bytes of the string
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns the bytes of the string.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+
+    /// Returns the string if it is valid UTF-8.
+    pub fn to_str(&self) -> Result<&str, std::str::Utf8Error> {
+        std::str::from_utf8(&self.0)
+    }
+
+    /// Converts the string into a `String` if it is valid UTF-8.
+    pub fn into_string(self) -> Result<String, std::string::FromUtf8Error> {
+        String::from_utf8(self.0)
+    }
+
+    /// Converts the string, replacing invalid UTF-8 with `U+FFFD REPLACEMENT CHARACTER`.
+    pub fn to_string_lossy(&self) -> std::borrow::Cow<'_, str> {
+        String::from_utf8_lossy(&self.0)
+    }
+}
+
+impl Deref for XdrString {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for XdrString {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<Vec<u8>> for XdrString {
+    fn from(bytes: Vec<u8>) -> Self {
+        XdrString(bytes)
+    }
+}
+
+impl From<&[u8]> for XdrString {
+    fn from(bytes: &[u8]) -> Self {
+        XdrString(bytes.to_vec())
+    }
+}
+
+impl From<String> for XdrString {
+    fn from(string: String) -> Self {
+        XdrString(string.into_bytes())
+    }
+}
+
+impl From<&str> for XdrString {
+    fn from(string: &str) -> Self {
+        XdrString(string.as_bytes().to_vec())
+    }
+}
+
+impl From<XdrString> for Vec<u8> {
+    fn from(string: XdrString) -> Self {
+        string.0
+    }
+}
+
+impl XdrSerialize for XdrString {
     fn len(&self) -> usize {
-        self.iter().map(|item| item.len()).sum()
+        XdrSerialize::len(self.as_bytes())
     }
 
-    fn serialize(&self, mut writer: impl Write) -> io::Result<()> {
-        for item in self {
-            item.serialize(&mut writer)?;
-        }
-        Ok(())
+    fn serialize(&self, writer: impl Write) -> io::Result<()> {
+        self.as_bytes().serialize(writer)
     }
 }
 
-impl<T: XdrDeserialize, const LEN: usize> XdrDeserialize for [T; LEN] {
-    fn deserialize(mut reader: impl Read) -> io::Result<Self> {
-        let mut vec = Vec::with_capacity(LEN);
-        for _ in 0..LEN {
-            vec.push(T::deserialize(&mut reader)?);
+impl XdrDeserialize for XdrString {
+    fn deserialize(reader: impl Read) -> io::Result<Self> {
+        Vec::<u8>::deserialize(reader).map(XdrString)
+    }
+}
+
+/// Quadruple-precision floating-point number (IEEE 754 binary128)
+///
+/// Rust has no 128-bit float, so the value is kept as its encoding. It can be converted from and
+/// to `f64`, the conversion to `f64` rounds to the nearest value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Quadruple(u128);
+
+impl Quadruple {
+    const EXPONENT_BIAS: i32 = 16383;
+    const MANTISSA_BITS: u32 = 112;
+    const MANTISSA_MASK: u128 = (1 << Self::MANTISSA_BITS) - 1;
+
+    /// Creates a number from its raw binary128 representation.
+    pub const fn from_bits(bits: u128) -> Self {
+        Self(bits)
+    }
+
+    /// Raw binary128 representation
+    pub const fn to_bits(self) -> u128 {
+        self.0
+    }
+
+    /// Creates a number from its big-endian encoding, which is the XDR encoding.
+    pub const fn from_be_bytes(bytes: [u8; 16]) -> Self {
+        Self(u128::from_be_bytes(bytes))
+    }
+
+    /// Big-endian encoding, which is the XDR encoding
+    pub const fn to_be_bytes(self) -> [u8; 16] {
+        self.0.to_be_bytes()
+    }
+}
+
+/// Exact conversion
+impl From<f64> for Quadruple {
+    fn from(value: f64) -> Self {
+        let bits = value.to_bits();
+        let sign = u128::from(bits >> 63) << 127;
+        let exponent = ((bits >> 52) & 0x7ff) as i32;
+        let mantissa = u128::from(bits & ((1 << 52) - 1));
+        let (exponent, mantissa) = match exponent {
+            // Infinity and NaN
+            0x7ff => (0x7fff, mantissa),
+            0 if mantissa == 0 => (0, 0),
+            // Subnormal numbers of f64 are normal numbers of binary128
+            0 => {
+                let msb = 127 - mantissa.leading_zeros() as i32;
+                let mantissa = (mantissa << (52 - msb)) & ((1 << 52) - 1);
+                (msb - 1074 + Self::EXPONENT_BIAS, mantissa)
+            }
+            _ => (exponent - 1023 + Self::EXPONENT_BIAS, mantissa),
+        };
+        Self(sign | (exponent as u128) << Self::MANTISSA_BITS | mantissa << 60)
+    }
+}
+
+/// Rounds to the nearest `f64`, ties to even
+impl From<Quadruple> for f64 {
+    fn from(value: Quadruple) -> Self {
+        let bits = value.0;
+        let sign = ((bits >> 127) as u64) << 63;
+        let exponent = ((bits >> Quadruple::MANTISSA_BITS) & 0x7fff) as i32;
+        let mantissa = bits & Quadruple::MANTISSA_MASK;
+        if exponent == 0x7fff {
+            let payload = (mantissa >> 60) as u64;
+            let nan = if mantissa == 0 { 0 } else { payload | 1 << 51 };
+            return f64::from_bits(sign | 0x7ff << 52 | nan);
+        }
+        if exponent == 0 {
+            // Subnormal numbers of binary128 are far below the smallest f64
+            return f64::from_bits(sign);
+        }
+
+        // Significand with the implicit bit, shifted to the precision of f64
+        let significand = 1 << Quadruple::MANTISSA_BITS | mantissa;
+        let unbiased = exponent - Quadruple::EXPONENT_BIAS;
+        let shift = if unbiased >= -1022 {
+            60
+        } else {
+            (60 - 1022 - unbiased) as u32
+        };
+        if shift > Quadruple::MANTISSA_BITS + 1 {
+            return f64::from_bits(sign);
+        }
+        let mut rounded = significand >> shift;
+        let remainder = significand & ((1 << shift) - 1);
+        let half = 1 << (shift - 1);
+        if remainder > half || (remainder == half && rounded & 1 == 1) {
+            rounded += 1;
         }
-        vec.try_into().map_err(|_| unreachable!())
+
+        let bits = if unbiased >= -1022 {
+            // Rounding may carry into the exponent, which may overflow into infinity
+            let exponent = (unbiased + 1023) as u64 + (rounded >> 53) as u64;
+            if exponent >= 0x7ff {
+                0x7ff << 52
+            } else {
+                exponent << 52 | (rounded as u64 & ((1 << 52) - 1))
+            }
+        } else {
+            // Subnormal, a carry makes it the smallest normal number
+            rounded as u64
+        };
+        f64::from_bits(sign | bits)
     }
 }
 
-/// Variable-Length Array
-impl<T: XdrSerialize> XdrSerialize for [T] {
+impl XdrSerialize for Quadruple {
     fn len(&self) -> usize {
-        (self.len() as u32).len() + self.iter().map(|item| item.len()).sum::<usize>()
+        mem::size_of::<u128>()
     }
 
     fn serialize(&self, mut writer: impl Write) -> io::Result<()> {
-        (self.len() as u32).serialize(&mut writer)?;
-        for item in self {
-            item.serialize(&mut writer)?;
-        }
-        Ok(())
+        writer.write_all(&self.to_be_bytes())
     }
 }
 
-impl<T: XdrSerialize> XdrSerialize for Vec<T> {
+impl XdrDeserialize for Quadruple {
+    fn deserialize(mut reader: impl Read) -> io::Result<Self> {
+        let mut buf = [0; 16];
+        reader.read_exact(&mut buf)?;
+        Ok(Self::from_be_bytes(buf))
+    }
+}
+
+/// Boolean, encoded like the enum `{ FALSE = 0, TRUE = 1 }`
+impl XdrSerialize for bool {
     fn len(&self) -> usize {
-        XdrSerialize::len(self.as_slice())
+        0u32.len()
     }
 
     fn serialize(&self, writer: impl Write) -> io::Result<()> {
-        self.as_slice().serialize(writer)
+        u32::from(*self).serialize(writer)
+    }
+
+    fn encode_into(&self, buf: &mut [u8]) -> io::Result<usize> {
+        u32::from(*self).encode_into(buf)
     }
 }
 
-impl<T: XdrDeserialize> XdrDeserialize for Vec<T> {
-    fn deserialize(mut reader: impl Read) -> io::Result<Self> {
-        let len = u32::deserialize(&mut reader)? as usize;
-        let mut this = Vec::with_capacity(len);
-        for _ in 0..len {
-            this.push(T::deserialize(&mut reader)?);
-        }
-        Ok(this)
+impl XdrDeserialize for bool {
+    fn deserialize(reader: impl Read) -> io::Result<Self> {
+        decode_bool(u32::deserialize(reader)?)
+    }
+
+    fn decode_from(bytes: &[u8]) -> io::Result<(Self, usize)> {
+        let (value, read) = u32::decode_from(bytes)?;
+        Ok((decode_bool(value)?, read))
     }
 }
 
-impl XdrSerialize for str {
+fn decode_bool(value: u32) -> io::Result<bool> {
+    match value {
+        0 => Ok(false),
+        1 => Ok(true),
+        value => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid value {} of bool", value),
+        )),
+    }
+}
+
+/// Unicode scalar value, encoded as an unsigned integer
+impl XdrSerialize for char {
     fn len(&self) -> usize {
-        (self.len() as u32).len() + self.len() + padding(self.len())
+        0u32.len()
     }
 
-    fn serialize(&self, mut writer: impl Write) -> io::Result<()> {
-        assert!(self.is_ascii());
-        (self.len() as u32).serialize(&mut writer)?;
-        writer.write_all(self.as_bytes())?;
-        writer.write_all(&[0u8; 3][..padding(self.len())])
+    fn serialize(&self, writer: impl Write) -> io::Result<()> {
+        u32::from(*self).serialize(writer)
     }
 }
 
-impl XdrSerialize for String {
+impl XdrDeserialize for char {
+    fn deserialize(reader: impl Read) -> io::Result<Self> {
+        let value = u32::deserialize(reader)?;
+        char::from_u32(value).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid value {} of char", value),
+            )
+        })
+    }
+}
+
+/// Lengths and indices, encoded as an unsigned integer. Values that don't fit into 32 bits can't
+/// be serialized.
+impl XdrSerialize for usize {
     fn len(&self) -> usize {
-        XdrSerialize::len(self.as_str())
+        0u32.len()
     }
 
     fn serialize(&self, writer: impl Write) -> io::Result<()> {
-        self.as_str().serialize(writer)
+        let value = u32::try_from(*self).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} doesn't fit into an unsigned int", self),
+            )
+        })?;
+        value.serialize(writer)
     }
 }
 
-impl XdrDeserialize for String {
+impl XdrDeserialize for usize {
     fn deserialize(reader: impl Read) -> io::Result<Self> {
-        let vec = Vec::<u8>::deserialize(reader)?;
-        Ok(Self::from_utf8(vec).unwrap())
+        Ok(u32::deserialize(reader)? as usize)
+    }
+}
+
+/// Converts a decoded `int` or `unsigned int` into the smaller type `name`.
+fn narrow<T: TryFrom<W>, W: Copy + std::fmt::Display>(value: W, name: &str) -> io::Result<T> {
+    T::try_from(value).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid value {} of {}", value, name),
+        )
+    })
+}
+
+/// Integers smaller than 32 bits are widened to `int` or `unsigned int`, like `char` and `short`
+/// by `rpcgen`. Decoded values that are out of range are rejected.
+///
+/// `u8` is not implemented, because sequences of bytes are opaque data.
+macro_rules! impl_xdr_widened {
+    ($Ty:ty, $Wide:ty) => {
+        impl XdrSerialize for $Ty {
+            fn len(&self) -> usize {
+                mem::size_of::<$Wide>()
+            }
+
+            fn serialize(&self, writer: impl Write) -> io::Result<()> {
+                <$Wide>::from(*self).serialize(writer)
+            }
+
+            fn encode_into(&self, buf: &mut [u8]) -> io::Result<usize> {
+                <$Wide>::from(*self).encode_into(buf)
+            }
+        }
+
+        impl XdrDeserialize for $Ty {
+            fn deserialize(reader: impl Read) -> io::Result<Self> {
+                narrow(<$Wide>::deserialize(reader)?, stringify!($Ty))
+            }
+
+            fn decode_from(bytes: &[u8]) -> io::Result<(Self, usize)> {
+                let (value, read) = <$Wide>::decode_from(bytes)?;
+                Ok((narrow(value, stringify!($Ty))?, read))
+            }
+        }
+    };
+}
+
+impl_xdr_widened!(i8, i32);
+impl_xdr_widened!(i16, i32);
+impl_xdr_widened!(u16, u32);
+impl_xdr_widened!(XdrUChar, u32);
+
+/// `unsigned char`, encoded as a 4-byte word
+///
+/// `u8` is reserved for opaque data, so `unsigned char` is mapped to this type instead. Decoded
+/// values above 255 are rejected, like those of the other widened integers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct XdrUChar(pub u8);
+
+impl From<u8> for XdrUChar {
+    fn from(value: u8) -> Self {
+        XdrUChar(value)
+    }
+}
+
+impl From<XdrUChar> for u8 {
+    fn from(value: XdrUChar) -> Self {
+        value.0
+    }
+}
+
+impl From<XdrUChar> for u32 {
+    fn from(value: XdrUChar) -> Self {
+        value.0.into()
+    }
+}
+
+impl TryFrom<u32> for XdrUChar {
+    type Error = std::num::TryFromIntError;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        u8::try_from(value).map(XdrUChar)
     }
 }
 
+/// Size of the stack buffer that arrays of numbers are converted in
+const CHUNK_BYTES: usize = 4096;
+
+/// Numbers, encoded as big-endian bytes. Arrays of numbers are byte-swapped in chunks of
+/// [`CHUNK_BYTES`], so that the writer and reader are called once per chunk.
 macro_rules! impl_xdr_be_bytes {
     ($Ty:ty) => {
         impl XdrSerialize for $Ty {
@@ -209,6 +1531,33 @@ macro_rules! impl_xdr_be_bytes {
             fn serialize(&self, mut writer: impl Write) -> io::Result<()> {
                 writer.write_all(&self.to_be_bytes())
             }
+
+            #[inline]
+            fn encode_into(&self, buf: &mut [u8]) -> io::Result<usize> {
+                put(buf, &self.to_be_bytes())
+            }
+
+            fn serialize_slice(items: &[Self], mut writer: impl Write) -> io::Result<()> {
+                const SIZE: usize = mem::size_of::<$Ty>();
+                let mut buf = [0; CHUNK_BYTES];
+                for chunk in items.chunks(CHUNK_BYTES / SIZE) {
+                    let written = Self::encode_slice_into(chunk, &mut buf)?;
+                    writer.write_all(&buf[..written])?;
+                }
+                Ok(())
+            }
+
+            fn encode_slice_into(items: &[Self], buf: &mut [u8]) -> io::Result<usize> {
+                const SIZE: usize = mem::size_of::<$Ty>();
+                let len = items.len() * SIZE;
+                let buf = buf
+                    .get_mut(..len)
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::WriteZero, "buffer too small"))?;
+                for (bytes, item) in buf.chunks_exact_mut(SIZE).zip(items) {
+                    bytes.copy_from_slice(&item.to_be_bytes());
+                }
+                Ok(len)
+            }
         }
 
         impl XdrDeserialize for $Ty {
@@ -217,6 +1566,44 @@ macro_rules! impl_xdr_be_bytes {
                 reader.read_exact(&mut buf)?;
                 Ok(Self::from_be_bytes(buf))
             }
+
+            #[inline]
+            fn decode_from(bytes: &[u8]) -> io::Result<(Self, usize)> {
+                Ok((Self::from_be_bytes(take(bytes)?), mem::size_of::<Self>()))
+            }
+
+            fn deserialize_slice(
+                items: &mut Vec<Self>,
+                len: usize,
+                mut reader: impl Read,
+            ) -> io::Result<()> {
+                const SIZE: usize = mem::size_of::<$Ty>();
+                let mut buf = [0; CHUNK_BYTES];
+                let mut remaining = len;
+                while remaining > 0 {
+                    let count = remaining.min(CHUNK_BYTES / SIZE);
+                    let bytes = &mut buf[..count * SIZE];
+                    reader.read_exact(bytes)?;
+                    Self::decode_slice_from(items, count, bytes)?;
+                    remaining -= count;
+                }
+                Ok(())
+            }
+
+            fn decode_slice_from(
+                items: &mut Vec<Self>,
+                len: usize,
+                bytes: &[u8],
+            ) -> io::Result<usize> {
+                const SIZE: usize = mem::size_of::<$Ty>();
+                let bytes = take_slice(bytes, len.saturating_mul(SIZE))?;
+                items.extend(
+                    bytes
+                        .chunks_exact(SIZE)
+                        .map(|chunk| Self::from_be_bytes(chunk.try_into().unwrap())),
+                );
+                Ok(bytes.len())
+            }
         }
     };
 }
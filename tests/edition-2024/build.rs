@@ -0,0 +1,9 @@
+fn main() {
+    rpc_lib_build::Config::new()
+        .file("../test.x")
+        .file("../xdr_types.x")
+        .server(true)
+        .stubs(true)
+        .generate()
+        .expect("Failed to generate code");
+}
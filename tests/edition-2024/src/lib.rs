@@ -0,0 +1,11 @@
+//! Builds the code generated by rpc-lib-build with edition 2024, the default of `cargo new`.
+
+#[allow(non_snake_case, non_camel_case_types)]
+pub mod test {
+    include!(concat!(env!("OUT_DIR"), "/test.rs"));
+}
+
+#[allow(non_snake_case, non_camel_case_types)]
+pub mod xdr_types {
+    include!(concat!(env!("OUT_DIR"), "/xdr_types.rs"));
+}
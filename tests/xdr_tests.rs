@@ -0,0 +1,867 @@
+use rpc_lib::{include_rpcl, XdrDeserializeBorrowed, XdrList, XdrUChar};
+
+// Encodings are taken from `rpcgen`-generated XDR-routines (libtirpc).
+#[include_rpcl("tests/xdr_types.x")]
+struct XdrTypes;
+
+fn serialize(value: &impl XdrSerialize) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    value.serialize(&mut bytes).expect("Serialization failed");
+    assert_eq!(
+        bytes.len(),
+        value.len(),
+        "len() doesn't match serialization"
+    );
+    assert_eq!(
+        value.serialize_to_vec().expect("Encoding failed"),
+        bytes,
+        "encode_into() doesn't match serialization"
+    );
+    bytes
+}
+
+#[rustfmt::skip]
+const ARRAYS: [u8; 72] = [
+    0, 0, 0, 3, 0, 0, 0, 1, 255, 255, 255, 254, 0, 0, 0, 3,
+    1, 2, 3, 4, 0, 0, 0, 5, 9, 8, 7, 6, 5, 0, 0, 0,
+    0, 0, 0, 5, 104, 101, 108, 108, 111, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 1, 255, 255, 255, 255, 255, 255, 255, 255, 0, 0, 0, 2,
+    0, 0, 0, 7, 0, 0, 0, 8,
+];
+
+#[test]
+fn struct_arrays() {
+    let arrays = Arrays::deserialize(&ARRAYS[..]).expect("Deserialization failed");
+    assert_eq!(arrays.vals, vec![1, -2, 3]);
+    assert_eq!(arrays.data, [1, 2, 3, 4]);
+    assert_eq!(arrays.blob, vec![9, 8, 7, 6, 5]);
+    assert_eq!(arrays.name, "hello");
+    assert_eq!(arrays.fixed, [1, u64::MAX]);
+    assert_eq!(arrays.ints, vec![7, 8]);
+    assert_eq!(serialize(&arrays), ARRAYS);
+}
+
+#[rustfmt::skip]
+const OPTIONALS: [u8; 64] = [
+    0, 0, 0, 1, 0, 0, 0, 42, 0, 0, 0, 0, 0, 0, 0, 1,
+    0, 0, 0, 1, 0, 0, 0, 1, 97, 0, 0, 0, 0, 0, 0, 1,
+    0, 0, 0, 2, 0, 0, 0, 2, 98, 98, 0, 0, 0, 0, 0, 1,
+    0, 0, 0, 3, 0, 0, 0, 1, 99, 0, 0, 0, 0, 0, 0, 0,
+];
+
+#[test]
+fn struct_optionals() {
+    let optionals = Optionals::deserialize(&OPTIONALS[..]).expect("Deserialization failed");
+    assert_eq!(optionals.number, Some(42));
+    assert_eq!(optionals.missing, None);
+    let mut names = Vec::new();
+    let mut next = &optionals.entries;
+    while let Some(entry) = next {
+        names.push((entry.fileid, entry.name.as_str()));
+        next = &entry.next;
+    }
+    assert_eq!(names, [(1, "a"), (2, "bb"), (3, "c")]);
+    assert_eq!(serialize(&optionals), OPTIONALS);
+}
+
+#[test]
+fn optional_invalid_discriminant() {
+    let bytes = [0, 0, 0, 2, 0, 0, 0, 42];
+    let err = Option::<i32>::deserialize(&bytes[..]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn linked_list_iterative() {
+    const LEN: u32 = 200_000;
+    let mut bytes = Vec::new();
+    for fileid in 0..LEN {
+        bytes.extend_from_slice(&fileid.to_be_bytes());
+        bytes.extend_from_slice(&[0, 0, 0, 0]); // empty name
+        let follows = if fileid + 1 < LEN { 1u32 } else { 0 };
+        bytes.extend_from_slice(&follows.to_be_bytes());
+    }
+
+    // Would overflow the stack if any of these were recursive
+    let list = entry::deserialize(&bytes[..]).expect("Deserialization failed");
+    assert_eq!(list.len(), bytes.len());
+    assert_eq!(serialize(&list), bytes);
+    let mut count = 0;
+    let mut next = Some(&list);
+    while let Some(entry) = next {
+        assert_eq!(entry.fileid, count);
+        count += 1;
+        next = entry.next.as_deref();
+    }
+    assert_eq!(count, LEN);
+    drop(list);
+
+    // Every node counts against the allocation budget
+    let mut context = rpc_lib::DecodeContext::new();
+    context.budget(std::mem::size_of::<entry>() * 1000);
+    let err = context.deserialize::<entry>(&bytes[..]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[derive(Debug, PartialEq, rpc_lib::XdrSerialize, rpc_lib::XdrDeserialize)]
+struct Entry {
+    fileid: u32,
+    name: String,
+}
+
+#[test]
+fn linked_list_as_vec() {
+    // `entries` of OPTIONALS
+    let bytes = &OPTIONALS[12..];
+    let list = XdrList::<Entry>::deserialize(bytes).expect("Deserialization failed");
+    let entries = [(1, "a"), (2, "bb"), (3, "c")].map(|(fileid, name)| Entry {
+        fileid,
+        name: name.to_string(),
+    });
+    assert_eq!(list.0, entries);
+    assert_eq!(serialize(&list), bytes);
+
+    let empty = XdrList::<Entry>::default();
+    assert_eq!(serialize(&empty), [0, 0, 0, 0]);
+}
+
+#[test]
+fn enum_values() {
+    let pixel =
+        Pixel::deserialize(&[255, 255, 255, 255, 0, 0, 0, 3][..]).expect("Deserialization failed");
+    assert_eq!(pixel.color, Color::BLUE);
+    assert_eq!(serialize(&Color::GREEN), [0, 0, 0, 8]);
+    assert_eq!(i32::from(Color::BLUE), -1);
+    assert_eq!(Color::try_from(8).unwrap(), Color::GREEN);
+
+    let err = Color::deserialize(&[0, 0, 0, 1][..]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(Color::try_from(2).is_err());
+}
+
+#[derive(Debug, PartialEq, rpc_lib::XdrSerialize, rpc_lib::XdrDeserialize)]
+enum Shade {
+    Red,
+    #[xdr(value = NAME_LEN as i32)]
+    Green,
+    Blue = -1,
+}
+
+#[derive(Debug, PartialEq, rpc_lib::XdrSerialize, rpc_lib::XdrDeserialize)]
+enum Reply {
+    #[xdr(case = 0)]
+    Pixel { color: Shade, x: i32 },
+    #[xdr(case = 2)]
+    Empty,
+    #[xdr(default)]
+    Unknown(i32),
+}
+
+#[test]
+fn derived_enums() {
+    assert_eq!(serialize(&Shade::Green), serialize(&Color::GREEN));
+    assert_eq!(serialize(&Shade::Blue), serialize(&Color::BLUE));
+    assert_eq!(Shade::deserialize(&[0, 0, 0, 0][..]).unwrap(), Shade::Red);
+    let err = Shade::deserialize(&[0, 0, 0, 1][..]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    let bytes = [0, 0, 0, 0, 255, 255, 255, 255, 0, 0, 0, 3];
+    let reply = Reply::deserialize(&bytes[..]).unwrap();
+    assert_eq!(
+        reply,
+        Reply::Pixel {
+            color: Shade::Blue,
+            x: 3
+        }
+    );
+    assert_eq!(serialize(&reply), bytes);
+    assert_eq!(serialize(&Reply::Empty), [0, 0, 0, 2]);
+    let unknown = Reply::deserialize(&[0, 0, 0, 7][..]).unwrap();
+    assert_eq!(unknown, Reply::Unknown(7));
+    assert_eq!(serialize(&unknown), [0, 0, 0, 7]);
+    let err = Reply::Unknown(2).serialize(Vec::new()).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    let err = Reply::Unknown(0).encode_into(&mut [0; 4]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[derive(Debug, PartialEq, rpc_lib::XdrSerialize, rpc_lib::XdrDeserialize)]
+struct Newtype(u32);
+
+#[derive(Debug, PartialEq, rpc_lib::XdrSerialize, rpc_lib::XdrDeserialize)]
+struct Void;
+
+/// Encodes an `u32` as `unsigned hyper`
+mod widened {
+    use std::io::{Read, Result, Write};
+
+    use rpc_lib::{XdrDeserialize, XdrSerialize};
+
+    pub fn len(_value: &u32) -> usize {
+        8
+    }
+
+    pub fn serialize(value: &u32, writer: impl Write) -> Result<()> {
+        u64::from(*value).serialize(writer)
+    }
+
+    pub fn deserialize(reader: impl Read) -> Result<u32> {
+        u64::deserialize(reader).map(|value| value as u32)
+    }
+}
+
+#[derive(Debug, PartialEq, rpc_lib::XdrSerialize, rpc_lib::XdrDeserialize)]
+struct Attributes {
+    #[xdr(skip)]
+    cached: Option<String>,
+    #[xdr(with = "widened")]
+    size: u32,
+    #[xdr(max_len = 2)]
+    vals: Vec<i32>,
+    #[xdr(opaque, max_len = 4)]
+    data: Box<[u8]>,
+}
+
+#[test]
+fn derived_structs() {
+    assert_eq!(serialize(&Newtype(5)), [0, 0, 0, 5]);
+    assert_eq!(Newtype::deserialize(&[0, 0, 0, 5][..]).unwrap(), Newtype(5));
+    assert!(serialize(&Void).is_empty());
+    assert_eq!(Void::deserialize(&[][..]).unwrap(), Void);
+
+    #[rustfmt::skip]
+    let bytes = [
+        0, 0, 0, 0, 0, 0, 0, 9, 0, 0, 0, 1, 0, 0, 0, 7,
+        0, 0, 0, 3, 1, 2, 3, 0,
+    ];
+    let attributes = Attributes::deserialize(&bytes[..]).unwrap();
+    assert_eq!(
+        attributes,
+        Attributes {
+            cached: None,
+            size: 9,
+            vals: vec![7],
+            data: vec![1, 2, 3].into(),
+        }
+    );
+    let attributes = Attributes {
+        cached: Some("ignored".into()),
+        ..attributes
+    };
+    assert_eq!(serialize(&attributes), bytes);
+
+    let too_long = Attributes {
+        vals: vec![1, 2, 3],
+        ..attributes
+    };
+    let mut bytes = Vec::new();
+    let err = too_long.serialize(&mut bytes).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(bytes.len(), 8, "data written beyond the invalid field");
+
+    #[rustfmt::skip]
+    let too_long = [
+        0, 0, 0, 0, 0, 0, 0, 9, 0, 0, 0, 0,
+        0, 0, 0, 5, 1, 2, 3, 4, 5, 0, 0, 0,
+    ];
+    let err = Attributes::deserialize(&too_long[..]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[derive(Debug, PartialEq, rpc_lib::XdrSerialize, rpc_lib::XdrDeserialize)]
+struct Pair<T, U>
+where
+    U: Clone,
+{
+    first: T,
+    second: U,
+}
+
+#[derive(Debug, PartialEq, rpc_lib::XdrDeserialize)]
+struct Borrowed<'a, T> {
+    name: &'a str,
+    data: &'a [u8],
+    rest: T,
+}
+
+#[test]
+fn derived_generics() {
+    let pair = Pair {
+        first: 1u32,
+        second: String::from("ab"),
+    };
+    let bytes = serialize(&pair);
+    assert_eq!(bytes, [0, 0, 0, 1, 0, 0, 0, 2, 97, 98, 0, 0]);
+    assert_eq!(Pair::deserialize(&bytes[..]).unwrap(), pair);
+
+    #[rustfmt::skip]
+    let bytes = [
+        0, 0, 0, 2, 97, 98, 0, 0, 0, 0, 0, 5, 1, 2, 3, 4,
+        5, 0, 0, 0, 0, 0, 0, 9, 0, 0, 0, 1,
+    ];
+    let mut input = &bytes[..];
+    let borrowed = Borrowed::<u32>::deserialize_borrowed(&mut input).unwrap();
+    assert_eq!(borrowed.name, "ab");
+    assert_eq!(borrowed.data, [1, 2, 3, 4, 5]);
+    assert_eq!(
+        borrowed.data.as_ptr(),
+        bytes[12..].as_ptr(),
+        "data was copied"
+    );
+    assert_eq!(borrowed.rest, 9);
+    assert_eq!(input, [0, 0, 0, 1]);
+
+    let err = Borrowed::<u32>::deserialize_borrowed(&mut &bytes[..14]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn union_discriminants() {
+    let red = ColorResult::deserialize(&[0, 0, 0, 0, 0, 0, 0, 5][..]).unwrap();
+    assert!(matches!(red, ColorResult::RED { red: 5 }));
+    assert_eq!(serialize(&red), [0, 0, 0, 0, 0, 0, 0, 5]);
+    let blue = ColorResult::deserialize(&[255, 255, 255, 255][..]).unwrap();
+    assert!(matches!(blue, ColorResult::CaseDefault(Color::BLUE)));
+    assert_eq!(serialize(&ColorResult::GREEN), [0, 0, 0, 8]);
+
+    let flag = Flag::deserialize(&[0, 0, 0, 1, 0, 0, 0, 3][..]).unwrap();
+    assert!(matches!(flag, Flag::TRUE { value: 3 }));
+    assert_eq!(serialize(&Flag::FALSE), [0, 0, 0, 0]);
+    let err = Flag::deserialize(&[0, 0, 0, 2][..]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    let code = Code::deserialize(&[0, 0, 0, 8, 0, 0, 0, 1, 120, 0, 0, 0][..]).unwrap();
+    assert!(matches!(&code, Code::NAME_LEN { text } if text == "x"));
+    assert_eq!(serialize(&code), [0, 0, 0, 8, 0, 0, 0, 1, 120, 0, 0, 0]);
+    assert_eq!(serialize(&Code::Case4294967295), [255, 255, 255, 255]);
+}
+
+#[test]
+fn union_default_arm() {
+    let bytes = [0, 0, 0, 5, 0, 0, 0, 2, 110, 111, 0, 0];
+    let status = Status::deserialize(&bytes[..]).unwrap();
+    assert!(matches!(
+        &status,
+        Status::CaseDefault { discriminant: 5, message } if message == "no"
+    ));
+    assert_eq!(serialize(&status), bytes);
+    assert_eq!(serialize(&Status::Case0), [0, 0, 0, 0]);
+    // The default arm can't carry a discriminant of another arm
+    let status = Status::CaseDefault {
+        discriminant: 0,
+        message: String::from("no"),
+    };
+    let err = status.serialize(Vec::new()).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+    let strict = Strict::deserialize(&[0, 0, 0, 1, 0, 0, 0, 2][..]).unwrap();
+    assert!(matches!(strict, Strict::Case1 { value: 2 }));
+    let err = Strict::deserialize(&[0, 0, 0, 2, 0, 0, 0, 2][..]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn union_grouped_labels() {
+    let bytes = [0, 0, 0, 2];
+    let grouped = Grouped::deserialize(&bytes[..]).unwrap();
+    assert!(matches!(grouped, Grouped::Case1(2)));
+    assert_eq!(serialize(&grouped), bytes);
+
+    let bytes = [0, 0, 0, 4, 0, 0, 0, 7];
+    let grouped = Grouped::deserialize(&bytes[..]).unwrap();
+    assert!(matches!(
+        grouped,
+        Grouped::Case3 {
+            discriminant: 4,
+            value: 7
+        }
+    ));
+    assert_eq!(serialize(&grouped), bytes);
+    assert!(Grouped::deserialize(&[0, 0, 0, 5][..]).is_err());
+
+    // The discriminant has to be one of the labels of the arm
+    let err = Grouped::Case1(3).serialize(Vec::new()).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    let grouped = Grouped::Case3 {
+        discriminant: 5,
+        value: 7,
+    };
+    let err = grouped.serialize(Vec::new()).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn anonymous_types() {
+    let nested = Nested {
+        point: Nested_point {
+            x: -1,
+            direction: Nested_point_direction::DOWN,
+        },
+        target: Nested_target::Case1 {
+            object: Nested_target_object { id: 3 },
+        },
+    };
+    let bytes = serialize(&nested);
+    assert_eq!(
+        bytes,
+        [255, 255, 255, 255, 0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 3]
+    );
+    let nested = Nested::deserialize(&bytes[..]).unwrap();
+    assert_eq!(nested.point.direction, Nested_point_direction::DOWN);
+    assert!(matches!(
+        nested.target,
+        Nested_target::Case1 {
+            object: Nested_target_object { id: 3 }
+        }
+    ));
+
+    assert_eq!(serialize(&Inline { a: 7 }), [0, 0, 0, 7]);
+    assert_eq!(
+        serialize(&ADD_arg1 { a: 1, b: 2 }),
+        [0, 0, 0, 1, 0, 0, 0, 2]
+    );
+    let result = ADD_result::deserialize(&[0, 0, 0, 3][..]).unwrap();
+    assert_eq!(result.sum, 3);
+}
+
+#[test]
+fn quadruple() {
+    use rpc_lib::Quadruple;
+
+    // 1.5 and -2^-1074, the smallest subnormal f64
+    #[rustfmt::skip]
+    let bytes = [
+        0x3f, 0xff, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 1,
+        0xbb, 0xcd, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    ];
+    let measurement = Measurement::deserialize(&bytes[..]).unwrap();
+    assert_eq!(f64::from(measurement.value), 1.5);
+    assert_eq!(f64::from(measurement.samples[0]), -f64::from_bits(1));
+    assert_eq!(serialize(&measurement), bytes);
+
+    for value in [
+        0.0,
+        -0.0,
+        1.0,
+        -3.25,
+        f64::MAX,
+        f64::MIN_POSITIVE,
+        1e-310,
+        f64::INFINITY,
+    ] {
+        let quadruple = Quadruple::from(value);
+        assert_eq!(f64::from(quadruple).to_bits(), value.to_bits());
+    }
+    assert!(f64::from(Quadruple::from(f64::NAN)).is_nan());
+    assert_eq!(Quadruple::from(1.0).to_be_bytes()[..2], [0x3f, 0xff]);
+
+    // 1 + 2^-53 is a tie and rounds to even, 1 + 2^-53 + 2^-112 rounds up
+    let tie = Quadruple::from_bits(0x3fff << 112 | 1 << 59);
+    assert_eq!(f64::from(tie), 1.0);
+    let above = Quadruple::from_bits(tie.to_bits() | 1);
+    assert_eq!(f64::from(above), 1.0 + f64::EPSILON);
+    // Too large and too small for f64
+    assert_eq!(
+        f64::from(Quadruple::from_bits(0x43ff << 112)),
+        f64::INFINITY
+    );
+    assert_eq!(f64::from(Quadruple::from_bits(0x0001 << 112)), 0.0);
+}
+
+#[test]
+fn small_integers() {
+    let small = SmallInts {
+        c: -2,
+        uc: XdrUChar(255),
+        s: -300,
+        us: 65535,
+        list: vec![1, -1],
+    };
+    #[rustfmt::skip]
+    let bytes = [
+        255, 255, 255, 254, 0, 0, 0, 255, 255, 255, 254, 212, 0, 0, 255, 255,
+        0, 0, 0, 2, 0, 0, 0, 1, 255, 255, 255, 255,
+    ];
+    assert_eq!(serialize(&small), bytes);
+    let decoded = SmallInts::deserialize(&bytes[..]).unwrap();
+    assert_eq!(
+        (decoded.c, decoded.uc, decoded.s, decoded.us),
+        (-2, XdrUChar(255), -300, 65535)
+    );
+    assert_eq!(decoded.list, [1, -1]);
+
+    let err = i8::deserialize(&[0, 0, 0, 128][..]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(u16::deserialize(&[0, 1, 0, 0][..]).is_err());
+    assert!(XdrUChar::deserialize(&[0, 0, 1, 0][..]).is_err());
+
+    assert_eq!(serialize(&'é'), [0, 0, 0, 0xe9]);
+    assert_eq!(char::deserialize(&[0, 0, 0, 0x41][..]).unwrap(), 'A');
+    assert!(char::deserialize(&[0, 0, 0xd8, 0][..]).is_err());
+
+    assert_eq!(serialize(&7usize), [0, 0, 0, 7]);
+    assert_eq!(usize::deserialize(&[0, 0, 1, 0][..]).unwrap(), 256);
+    assert_eq!(
+        (u32::MAX as usize + 1)
+            .serialize(Vec::new())
+            .unwrap_err()
+            .kind(),
+        std::io::ErrorKind::InvalidInput
+    );
+
+    assert_eq!(serialize(&true), [0, 0, 0, 1]);
+    assert!(bool::deserialize(&[0, 0, 0, 2][..]).is_err());
+}
+
+#[test]
+fn max_lengths() {
+    use std::io::ErrorKind::InvalidData;
+
+    // `int vals<10>`
+    let mut arrays = Arrays::deserialize(&ARRAYS[..]).unwrap();
+    arrays.vals = vec![0; 11];
+    let mut bytes = Vec::new();
+    assert_eq!(
+        arrays.serialize(&mut bytes).unwrap_err().kind(),
+        InvalidData
+    );
+    assert!(bytes.is_empty());
+    let mut bytes = ARRAYS;
+    bytes[3] = 11;
+    assert_eq!(
+        Arrays::deserialize(&bytes[..]).unwrap_err().kind(),
+        InvalidData
+    );
+
+    // Union arms and typedefs with a maximum length
+    let bounded = Bounded::Case1 { values: vec![1, 2] };
+    assert_eq!(
+        serialize(&bounded),
+        [0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2]
+    );
+    let bounded = Bounded::Case1 {
+        values: vec![1, 2, 3],
+    };
+    assert!(bounded.serialize(Vec::new()).is_err());
+    let bytes = [0, 0, 0, 2, 0, 0, 0, 9, 0, 0, 0, 0];
+    assert_eq!(
+        Bounded::deserialize(&bytes[..]).unwrap_err().kind(),
+        InvalidData
+    );
+    let named = Named {
+        name: "too long!".into(),
+    };
+    assert!(named.serialize(Vec::new()).is_err());
+    assert_eq!(
+        Named::deserialize(&bytes[4..]).unwrap_err().kind(),
+        InvalidData
+    );
+
+    // Unbounded data is limited by the maximum allocation
+    assert!(rpc_lib::max_allocation() < u32::MAX as usize);
+    let huge = [255, 255, 255, 255];
+    assert_eq!(
+        Vec::<u8>::deserialize(&huge[..]).unwrap_err().kind(),
+        InvalidData
+    );
+    assert_eq!(
+        String::deserialize(&huge[..]).unwrap_err().kind(),
+        InvalidData
+    );
+    assert_eq!(
+        Vec::<u64>::deserialize(&[0, 255, 255, 255][..])
+            .unwrap_err()
+            .kind(),
+        InvalidData
+    );
+}
+
+#[test]
+fn decode_hardening() {
+    use rpc_lib::DecodeContext;
+    use std::io::ErrorKind::InvalidData;
+
+    // Padding of fixed-length opaque data is consumed and has to be zero
+    let bytes = [1, 2, 3, 0, 0, 0, 0, 9];
+    let mut reader = &bytes[..];
+    assert_eq!(<[u8; 3]>::deserialize(&mut reader).unwrap(), [1, 2, 3]);
+    assert_eq!(u32::deserialize(&mut reader).unwrap(), 9);
+    let err = <[u8; 3]>::deserialize(&[1, 2, 3, 4][..]).unwrap_err();
+    assert_eq!(err.kind(), InvalidData);
+
+    // Variable-length opaque data and strings
+    let bytes = [0, 0, 0, 1, 97, 0, 1, 0];
+    assert_eq!(
+        Vec::<u8>::deserialize(&bytes[..]).unwrap_err().kind(),
+        InvalidData
+    );
+    assert_eq!(
+        String::deserialize(&bytes[..]).unwrap_err().kind(),
+        InvalidData
+    );
+    let mut reader = &bytes[..];
+    assert!(<&str>::deserialize_borrowed(&mut reader).is_err());
+    let mut lenient = DecodeContext::new();
+    lenient.strict_padding(false);
+    assert_eq!(lenient.deserialize::<String>(&bytes[..]).unwrap(), "a");
+    let mut reader = &bytes[..];
+    assert_eq!(
+        lenient.deserialize_borrowed::<&[u8]>(&mut reader).unwrap(),
+        b"a"
+    );
+    // The context only applies within `deserialize`
+    assert!(String::deserialize(&bytes[..]).is_err());
+
+    let invalid_utf8 = [0, 0, 0, 2, 0xc3, 0x28, 0, 0];
+    assert_eq!(
+        String::deserialize(&invalid_utf8[..]).unwrap_err().kind(),
+        InvalidData
+    );
+
+    // The budget covers all variable-length data of a value
+    let nested = [0, 0, 0, 2, 0, 0, 0, 3, 1, 2, 3, 0, 0, 0, 0, 3, 4, 5, 6, 0];
+    let mut context = DecodeContext::new();
+    context.budget(std::mem::size_of::<Vec<u8>>() * 2 + 5);
+    assert_eq!(
+        context
+            .deserialize::<Vec<Vec<u8>>>(&nested[..])
+            .unwrap_err()
+            .kind(),
+        InvalidData
+    );
+    context.budget(std::mem::size_of::<Vec<u8>>() * 2 + 6);
+    assert_eq!(
+        context.deserialize::<Vec<Vec<u8>>>(&nested[..]).unwrap(),
+        [[1, 2, 3], [4, 5, 6]]
+    );
+    context.max_allocation(2);
+    assert!(context.deserialize::<Vec<u8>>(&nested[4..]).is_err());
+
+    // Truncated input
+    assert!(Arrays::deserialize(&ARRAYS[..40]).is_err());
+}
+
+#[test]
+fn byte_strings() {
+    use rpc_lib::XdrString;
+
+    // "café" in Latin-1
+    let bytes = [0, 0, 0, 4, 99, 97, 102, 0xe9];
+    let string = XdrString::deserialize(&bytes[..]).unwrap();
+    assert_eq!(string.as_bytes(), b"caf\xe9");
+    assert_eq!(string.to_string_lossy(), "caf\u{fffd}");
+    assert!(string.to_str().is_err());
+    assert!(string.clone().into_string().is_err());
+    assert_eq!(serialize(&string), bytes);
+    assert!(String::deserialize(&bytes[..]).is_err());
+
+    let string = XdrString::from("café");
+    assert_eq!(string.to_str().unwrap(), "café");
+    assert_eq!(
+        serialize(&string),
+        [0, 0, 0, 5, 99, 97, 102, 0xc3, 0xa9, 0, 0, 0]
+    );
+    // Non-ASCII `str`s are encoded as UTF-8
+    assert_eq!(serialize(&"café"), serialize(&string));
+    assert_eq!(
+        String::deserialize(&serialize(&string)[..]).unwrap(),
+        "café"
+    );
+}
+
+#[test]
+fn containers() {
+    use std::borrow::Cow;
+    use std::collections::{BTreeMap, HashMap, VecDeque};
+    use std::io::ErrorKind::InvalidData;
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    assert_eq!(serialize(&Rc::new(7u32)), [0, 0, 0, 7]);
+    assert_eq!(*Arc::<u32>::deserialize(&[0, 0, 0, 7][..]).unwrap(), 7);
+    let bytes = [0, 0, 0, 2, 104, 105, 0, 0];
+    assert_eq!(serialize(&Cow::Borrowed("hi")), bytes);
+    assert_eq!(serialize(&Cow::Borrowed(&b"hi"[..])), bytes);
+    assert_eq!(Cow::<str>::deserialize(&bytes[..]).unwrap(), "hi");
+    assert_eq!(Cow::<[u8]>::deserialize(&bytes[..]).unwrap(), &b"hi"[..]);
+
+    let deque = VecDeque::from(vec![1u32, 2]);
+    assert_eq!(serialize(&deque), serialize(&vec![1u32, 2]));
+    assert_eq!(
+        VecDeque::<u32>::deserialize(&serialize(&deque)[..]).unwrap(),
+        deque
+    );
+
+    // Tuples are encoded like structs
+    let tuple = (1u32, true, String::from("a"));
+    let bytes = [0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 1, 97, 0, 0, 0];
+    assert_eq!(serialize(&tuple), bytes);
+    assert_eq!(
+        <(u32, bool, String)>::deserialize(&bytes[..]).unwrap(),
+        tuple
+    );
+
+    // Maps are encoded as arrays of pairs
+    let map = BTreeMap::from([(2u32, true), (1, false)]);
+    let bytes = [0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 1];
+    assert_eq!(serialize(&map), bytes);
+    assert_eq!(serialize(&vec![(1u32, false), (2, true)]), bytes);
+    assert_eq!(BTreeMap::deserialize(&bytes[..]).unwrap(), map);
+    let hash_map: HashMap<u32, bool> = HashMap::deserialize(&bytes[..]).unwrap();
+    assert_eq!(hash_map, HashMap::from([(1, false), (2, true)]));
+    assert_eq!(serialize(&hash_map).len(), bytes.len());
+    let duplicate = [0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1];
+    assert_eq!(
+        BTreeMap::<u32, bool>::deserialize(&duplicate[..])
+            .unwrap_err()
+            .kind(),
+        InvalidData
+    );
+    assert!(HashMap::<u32, bool>::deserialize(&duplicate[..]).is_err());
+}
+
+#[cfg(feature = "bytes")]
+#[test]
+fn bytes_opaque() {
+    let bytes = bytes::Bytes::from_static(b"abcde");
+    let encoded = serialize(&bytes);
+    assert_eq!(encoded, [0, 0, 0, 5, 97, 98, 99, 100, 101, 0, 0, 0]);
+    assert_eq!(bytes::Bytes::deserialize(&encoded[..]).unwrap(), bytes);
+}
+
+#[test]
+fn fixed_arrays() {
+    let arrays = FixedArrays {
+        raw: [1, 2, 3],
+        words: [XdrUChar(4), XdrUChar(5), XdrUChar(255)],
+        signed_words: [-1, 6],
+        names: [String::from("a"), String::from("bc")],
+    };
+    // Opaque bytes are packed, small integers take a word each
+    #[rustfmt::skip]
+    let bytes = [
+        1, 2, 3, 0, 0, 0, 0, 4, 0, 0, 0, 5, 0, 0, 0, 255,
+        255, 255, 255, 255, 0, 0, 0, 6, 0, 0, 0, 1, 97, 0, 0, 0,
+        0, 0, 0, 2, 98, 99, 0, 0,
+    ];
+    assert_eq!(serialize(&arrays), bytes);
+    let decoded = FixedArrays::deserialize(&bytes[..]).unwrap();
+    assert_eq!(decoded.raw, arrays.raw);
+    assert_eq!(decoded.words, arrays.words);
+    assert_eq!(decoded.signed_words, arrays.signed_words);
+    assert_eq!(decoded.names, arrays.names);
+
+    // Truncated in the middle of an array
+    let err = FixedArrays::deserialize(&bytes[..32]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    assert!(<[u32; 3]>::deserialize(&bytes[4..12]).is_err());
+}
+
+#[test]
+fn fixed_arrays_partially_decoded() {
+    use std::cell::Cell;
+
+    thread_local!(static DROPPED: Cell<usize> = const { Cell::new(0) });
+
+    struct Counted(u32);
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+        }
+    }
+
+    impl XdrDeserialize for Counted {
+        fn deserialize(reader: impl std::io::Read) -> std::io::Result<Self> {
+            u32::deserialize(reader).map(Counted)
+        }
+    }
+
+    // The elements decoded before the error are dropped exactly once
+    let bytes = [0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0];
+    assert!(<[Counted; 3]>::deserialize(&bytes[..]).is_err());
+    assert_eq!(DROPPED.with(Cell::get), 2);
+    assert!(<[Counted; 3]>::decode_from(&bytes).is_err());
+    assert_eq!(DROPPED.with(Cell::get), 4);
+
+    let (items, read) = <[Counted; 2]>::decode_from(&bytes).unwrap();
+    assert_eq!((items[0].0, items[1].0, read), (1, 2, 8));
+    drop(items);
+    assert_eq!(DROPPED.with(Cell::get), 6);
+}
+
+#[test]
+fn slice_encoding() {
+    #[rustfmt::skip]
+    let bytes = [
+        0, 0, 0, 0, 0, 0, 0, 9, 0, 0, 0, 1, 0, 0, 0, 7,
+        0, 0, 0, 3, 1, 2, 3, 0,
+    ];
+    let attributes = Attributes::deserialize(&bytes[..]).unwrap();
+    let mut buf = [0xff; 32];
+    assert_eq!(attributes.encode_into(&mut buf).unwrap(), bytes.len());
+    assert_eq!(buf[..bytes.len()], bytes);
+    let err = attributes.encode_into(&mut buf[..20]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::WriteZero);
+    let too_long = Attributes {
+        vals: vec![1, 2, 3],
+        ..attributes
+    };
+    let err = too_long.encode_into(&mut buf).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    // Trailing bytes are left for the caller
+    let mut input = bytes.to_vec();
+    input.extend([0, 0, 0, 1]);
+    let (decoded, read) = Attributes::decode_from(&input).unwrap();
+    assert_eq!(read, bytes.len());
+    assert_eq!(decoded, Attributes::deserialize(&bytes[..]).unwrap());
+    let err = Attributes::decode_from(&bytes[..22]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+
+    let arrays = Arrays::decode_from(&ARRAYS).unwrap();
+    assert_eq!(arrays.1, ARRAYS.len());
+    assert_eq!(arrays.0.serialize_to_vec().unwrap(), ARRAYS);
+    assert!(Arrays::decode_from(&ARRAYS[..40]).is_err());
+    assert_eq!(
+        u32::decode_from(&[0, 0, 0, 7, 1]).unwrap(),
+        (7, 4),
+        "trailing bytes were read"
+    );
+    assert_eq!(
+        <Option<[u8; 2]>>::decode_from(&[0, 0, 0, 1, 5, 6, 0, 0]).unwrap(),
+        (Some([5, 6]), 8)
+    );
+    let err = <[u8; 2]>::decode_from(&[5, 6, 0, 1]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn numeric_arrays() {
+    // Spans several chunks of the bulk conversion
+    let values: Vec<u32> = (0..3000).collect();
+    let bytes = serialize(&values);
+    assert_eq!(bytes[..4], [0, 0, 0x0b, 0xb8]);
+    assert_eq!(bytes[4 + 4 * 1025..][..4], [0, 0, 4, 1]);
+    assert_eq!(Vec::<u32>::deserialize(&bytes[..]).unwrap(), values);
+    assert_eq!(
+        Vec::<u32>::decode_from(&bytes).unwrap(),
+        (values, bytes.len())
+    );
+
+    let samples: Vec<f64> = (0..1000).map(|i| f64::from(i) * -0.5).collect();
+    let bytes = serialize(&samples);
+    assert_eq!(bytes[4 + 8..][..8], (-0.5f64).to_be_bytes());
+    assert_eq!(Vec::<f64>::deserialize(&bytes[..]).unwrap(), samples);
+    let floats = [1.5f32; 5];
+    assert_eq!(
+        <[f32; 5]>::deserialize(&serialize(&floats)[..]).unwrap(),
+        floats
+    );
+
+    let err = Vec::<f64>::deserialize(&bytes[..bytes.len() - 1]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    let err = Vec::<f64>::decode_from(&bytes[..bytes.len() - 1]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+}
@@ -0,0 +1,140 @@
+const NAME_LEN = 8;
+
+typedef int Ints<>;
+
+struct Arrays {
+    int vals<10>;
+    opaque data[4];
+    opaque blob<>;
+    string name<NAME_LEN>;
+    unsigned hyper fixed[2];
+    Ints ints;
+};
+
+struct entry {
+    unsigned int fileid;
+    string name<>;
+    entry *next;
+};
+
+struct Optionals {
+    int *number;
+    int *missing;
+    entry *entries;
+};
+
+enum Color {
+    RED = 0,
+    GREEN = NAME_LEN,
+    BLUE = -1
+};
+
+struct Pixel {
+    Color color;
+    int x;
+};
+
+struct SmallInts {
+    char c;
+    unsigned char uc;
+    short s;
+    unsigned short us;
+    short list<>;
+};
+
+struct FixedArrays {
+    opaque raw[3];
+    unsigned char words[3];
+    char signed_words[2];
+    string names[2];
+};
+
+typedef string Name<NAME_LEN>;
+
+union Bounded switch (int code) {
+    case 1:
+        int values<2>;
+    case 2:
+        Name name;
+};
+
+struct Named {
+    Name name;
+};
+
+struct Measurement {
+    quadruple value;
+    quadruple samples<>;
+};
+
+union ColorResult switch (Color color) {
+    case RED:
+        int red;
+    case GREEN:
+        void;
+    default:
+        void;
+};
+
+union Flag switch (bool set) {
+    case TRUE:
+        unsigned int value;
+    case FALSE:
+        void;
+};
+
+union Code switch (unsigned int code) {
+    case NAME_LEN:
+        string text<>;
+    case 0xffffffff:
+        void;
+    default:
+        void;
+};
+
+union Status switch (int code) {
+    case 0:
+        void;
+    default:
+        string message<>;
+};
+
+union Strict switch (int code) {
+    case 1:
+        int value;
+};
+
+union Grouped switch (int code) {
+    case 1:
+    case 2:
+        void;
+    case 3:
+    case 4:
+        int value;
+};
+
+struct Nested {
+    struct {
+        int x;
+        enum { UP = 1, DOWN = 2 } direction;
+    } point;
+    union switch (int kind) {
+        case 1:
+            struct { hyper id; } object;
+        default:
+            void;
+    } target;
+};
+
+typedef struct {
+    unsigned int a;
+} Inline;
+
+program XDR_TYPES {
+    version VERS {
+        Arrays ECHO_ARRAYS(Arrays) = 1;
+        Color MIX(Color, Color) = 2;
+        struct { int sum; } ADD(struct { int a; int b; }) = 3;
+        Name RENAME(Name) = 4;
+    } = 1;
+} = 500001;